@@ -6,7 +6,7 @@ use std::{
 
 /// Trait for implementing a reallocation count tracker.
 ///
-/// # Safety requirements
+/// # Safety
 ///
 /// 1. [`Balance::check`] must return the same value until [`Balance::invalidate`] is called.
 /// 2. if [`Balance::exhausted`] returns false, then [`Balance::check`] returns numbers below 2^56
@@ -24,6 +24,23 @@ pub unsafe trait Balance: Sized {
 
     /// Get the current reallocation count value.
     fn check(&self) -> u64;
+
+    /// Mark the tracked allocation as poisoned, until it is disowned or the poison is cleared.
+    /// This must not change the output of [`Balance::check`].
+    ///
+    /// The default implementation does not support poisoning, and does nothing.
+    #[inline]
+    fn poison(&self) {}
+
+    /// Check whether the tracked allocation has been poisoned.
+    #[inline]
+    fn is_poisoned(&self) -> bool {
+        false
+    }
+
+    /// Clear the poison mark of the tracked allocation.
+    #[inline]
+    fn clear_poison(&self) {}
 }
 
 // SAFETY:
@@ -95,3 +112,82 @@ unsafe impl Balance for AtomicI32 {
         self.load(Ordering::Relaxed) as u64
     }
 }
+
+/// The flag kept in the spare bits of a 64-bit count while poisoned.
+const POISONED: u64 = 1 << 63;
+
+/// A 64-bit [`Balance`] which supports poisoning, keeping the flag in the bits above
+/// the reallocation count.
+#[derive(Default)]
+#[repr(transparent)]
+pub struct Poisoning<B>(B);
+
+impl<B> Poisoning<B> {
+    pub const fn new(balance: B) -> Self {
+        Self(balance)
+    }
+}
+
+// SAFETY:
+// 1. Only the flag changes outside of `invalidate`, and it is masked out
+// 2. True by default implementation
+unsafe impl Balance for Poisoning<Cell<u64>> {
+    #[inline]
+    fn invalidate(&self) {
+        self.0.set((self.0.get() & !POISONED) + 1)
+    }
+
+    #[inline]
+    fn check(&self) -> u64 {
+        self.0.get() & !POISONED
+    }
+
+    #[inline]
+    fn poison(&self) {
+        self.0.set(self.0.get() | POISONED)
+    }
+
+    #[inline]
+    fn is_poisoned(&self) -> bool {
+        self.0.get() & POISONED != 0
+    }
+
+    #[inline]
+    fn clear_poison(&self) {
+        self.0.set(self.0.get() & !POISONED)
+    }
+}
+
+// SAFETY:
+// 1. Only the flag changes outside of `invalidate`, and it is masked out
+// 2. True by default implementation
+unsafe impl Balance for Poisoning<AtomicU64> {
+    #[inline]
+    fn invalidate(&self) {
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                Some((n & !POISONED) + 1)
+            });
+    }
+
+    #[inline]
+    fn check(&self) -> u64 {
+        self.0.load(Ordering::Relaxed) & !POISONED
+    }
+
+    #[inline]
+    fn poison(&self) {
+        self.0.fetch_or(POISONED, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn is_poisoned(&self) -> bool {
+        self.0.load(Ordering::Relaxed) & POISONED != 0
+    }
+
+    #[inline]
+    fn clear_poison(&self) {
+        self.0.fetch_and(!POISONED, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,306 @@
+use std::{
+    cell::Cell,
+    marker::PhantomData,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::accounts::permits::Permits;
+
+/// The upgradable permit is held.
+const UPGRADABLE: u32 = 1 << 31;
+/// The mutation permit is held.
+const WRITER: u32 = 1 << 30;
+/// A writer was turned away, and readers should make way for it.
+const WRITERS_WAITING: u32 = 1 << 29;
+/// A reader was turned away by a writer, and the next writer should make way for it.
+const READERS_WAITING: u32 = 1 << 28;
+/// The number of reference permits held.
+const READERS: u32 = READERS_WAITING - 1;
+const WAITING: u32 = WRITERS_WAITING | READERS_WAITING;
+
+/// A policy deciding who gets turned away when readers and writers contend for
+/// [`PolicyPermits`]. Policies are resolved at compile time.
+pub trait Policy: 'static {
+    /// Turn away new readers while a writer is waiting for readers to leave.
+    const YIELD_TO_WRITERS: bool;
+    /// Turn away the first writer after a write, if a reader was turned away during it.
+    const YIELD_TO_READERS: bool;
+}
+
+/// Readers are never turned away for writers, which may starve them.
+pub enum PreferReaders {}
+
+/// New readers are turned away while a writer is waiting, which may starve readers.
+pub enum PreferWriters {}
+
+/// Readers make way for waiting writers, and writers make way for readers turned away
+/// during the previous write, so that neither can starve the other.
+pub enum Fair {}
+
+impl Policy for PreferReaders {
+    const YIELD_TO_WRITERS: bool = false;
+    const YIELD_TO_READERS: bool = false;
+}
+
+impl Policy for PreferWriters {
+    const YIELD_TO_WRITERS: bool = true;
+    const YIELD_TO_READERS: bool = false;
+}
+
+impl Policy for Fair {
+    const YIELD_TO_WRITERS: bool = true;
+    const YIELD_TO_READERS: bool = true;
+}
+
+/// A counter which [`PolicyPermits`] can keep their state in.
+pub trait Counter: Default {
+    fn load(&self) -> u32;
+
+    /// Replace the value if it is still `current`, returning the value found either way.
+    fn compare_exchange(&self, current: u32, new: u32) -> Result<u32, u32>;
+}
+
+impl Counter for Cell<u32> {
+    #[inline]
+    fn load(&self) -> u32 {
+        self.get()
+    }
+
+    #[inline]
+    fn compare_exchange(&self, current: u32, new: u32) -> Result<u32, u32> {
+        let found = self.get();
+        if found == current {
+            self.set(new);
+            Ok(found)
+        } else {
+            Err(found)
+        }
+    }
+}
+
+impl Counter for AtomicU32 {
+    #[inline]
+    fn load(&self) -> u32 {
+        self.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    fn compare_exchange(&self, current: u32, new: u32) -> Result<u32, u32> {
+        self.compare_exchange_weak(current, new, Ordering::AcqRel, Ordering::Acquire)
+    }
+}
+
+/// Counting [`Permits`] with a contention [`Policy`], kept in a [`Counter`] such as
+/// `Cell<u32>` or `AtomicU32`.
+///
+/// The plain counter types implement [`Permits`] preferring readers themselves, so a
+/// policy only costs anything where it is chosen.
+pub struct PolicyPermits<C: Counter, P: Policy> {
+    count: C,
+    _policy: PhantomData<fn() -> P>,
+}
+
+pub type ReaderPreferring<C> = PolicyPermits<C, PreferReaders>;
+pub type WriterPreferring<C> = PolicyPermits<C, PreferWriters>;
+pub type EventuallyFair<C> = PolicyPermits<C, Fair>;
+
+impl<C: Counter, P: Policy> Default for PolicyPermits<C, P> {
+    fn default() -> Self {
+        Self {
+            count: C::default(),
+            _policy: PhantomData,
+        }
+    }
+}
+
+impl<C: Counter, P: Policy> PolicyPermits<C, P> {
+    /// Apply `f` to the state, which returns the new state and whether the permit
+    /// was granted.
+    #[inline]
+    fn transition(&self, f: impl Fn(u32) -> (u32, bool)) -> bool {
+        let mut current = self.count.load();
+        loop {
+            let (new, granted) = f(current);
+            if new == current {
+                return granted;
+            }
+            match self.count.compare_exchange(current, new) {
+                Ok(_) => return granted,
+                Err(found) => current = found,
+            }
+        }
+    }
+
+    /// Admit a reader or the upgradable permit by adding `add` to the state, unless the
+    /// policy says to make way for a writer.
+    #[inline]
+    fn admit_reader(n: u32, add: u32) -> (u32, bool) {
+        if n & WRITER != 0 {
+            let mark = if P::YIELD_TO_READERS {
+                READERS_WAITING
+            } else {
+                0
+            };
+            return (n | mark, false);
+        }
+
+        if P::YIELD_TO_WRITERS && n & WRITERS_WAITING != 0 {
+            if n & (READERS | UPGRADABLE) != 0 {
+                return (n, false);
+            }
+            // The readers are gone, so the writer has had its chance.
+            return ((n & !WRITERS_WAITING) + add, true);
+        }
+
+        ((n & !READERS_WAITING) + add, true)
+    }
+}
+
+// SAFETY:
+// 1. The mutation permit is only granted when no other permit is held, reference permits
+//    only when the mutation permit is not held, and the upgradable permit only when
+//    neither it nor the mutation permit is held
+unsafe impl<C: Counter, P: Policy> Permits for PolicyPermits<C, P> {
+    type UnderlyingLockableEntity = C;
+
+    #[inline]
+    unsafe fn underlying(&self) -> &Self::UnderlyingLockableEntity {
+        &self.count
+    }
+
+    #[inline]
+    fn try_reference(&self) -> bool {
+        self.transition(|n| {
+            if n & READERS == READERS {
+                return (n, false);
+            }
+            Self::admit_reader(n, 1)
+        })
+    }
+
+    #[inline]
+    fn try_mutation(&self) -> bool {
+        self.transition(|n| {
+            if n & !WAITING != 0 {
+                let mark = if P::YIELD_TO_WRITERS {
+                    WRITERS_WAITING
+                } else {
+                    0
+                };
+                return (n | mark, false);
+            }
+            if P::YIELD_TO_READERS && n & READERS_WAITING != 0 {
+                // Make way once for the readers turned away during the last write.
+                return (n & !READERS_WAITING, false);
+            }
+            (WRITER, true)
+        })
+    }
+
+    #[inline]
+    fn try_mutation_unfair(&self) -> bool {
+        self.transition(|n| {
+            if n & !WAITING != 0 {
+                (n, false)
+            } else {
+                (WRITER, true)
+            }
+        })
+    }
+
+    #[inline]
+    unsafe fn duplicate_reference(&self) -> bool {
+        // Must not make way for writers, as they wait for this very reader.
+        self.transition(|n| {
+            if n & READERS == READERS {
+                (n, false)
+            } else {
+                (n + 1, true)
+            }
+        })
+    }
+
+    #[inline]
+    unsafe fn try_escalate(&self) -> bool {
+        self.transition(|n| {
+            if n & !WAITING == 1 {
+                ((n & READERS_WAITING) | WRITER, true)
+            } else {
+                (n, false)
+            }
+        })
+    }
+
+    #[inline]
+    fn try_upgradable(&self) -> bool {
+        self.transition(|n| {
+            if n & UPGRADABLE != 0 {
+                return (n, false);
+            }
+            Self::admit_reader(n, UPGRADABLE)
+        })
+    }
+
+    #[inline]
+    unsafe fn try_upgrade(&self) -> bool {
+        self.transition(|n| {
+            if n & !WAITING == UPGRADABLE {
+                ((n & READERS_WAITING) | WRITER, true)
+            } else {
+                (n, false)
+            }
+        })
+    }
+
+    #[inline]
+    unsafe fn downgrade_upgradable(&self) {
+        self.transition(|n| ((n & !UPGRADABLE) + 1, true));
+    }
+
+    #[inline]
+    unsafe fn relax_to_upgradable(&self) {
+        self.transition(|n| ((n & WAITING) | UPGRADABLE, true));
+    }
+
+    #[inline]
+    unsafe fn abandon_upgradable(&self) {
+        self.transition(|n| (n & !UPGRADABLE, true));
+    }
+
+    #[inline]
+    unsafe fn relax_permit(&self) {
+        self.transition(|n| ((n & WAITING) | 1, true));
+    }
+
+    #[inline]
+    unsafe fn abandon_reference(&self) {
+        self.transition(|n| (n - 1, true));
+    }
+
+    #[inline]
+    unsafe fn abandon_reference_or_escalate(&self) -> bool {
+        self.transition(|n| {
+            if n & !WAITING == 1 {
+                ((n & READERS_WAITING) | WRITER, true)
+            } else {
+                (n - 1, false)
+            }
+        })
+    }
+
+    #[inline]
+    unsafe fn abandon_upgradable_or_upgrade(&self) -> bool {
+        self.transition(|n| {
+            if n & !WAITING == UPGRADABLE {
+                ((n & READERS_WAITING) | WRITER, true)
+            } else {
+                (n & !UPGRADABLE, false)
+            }
+        })
+    }
+
+    #[inline]
+    unsafe fn abandon_mutation(&self) {
+        self.transition(|n| (n & !WRITER, true));
+    }
+}
@@ -1,6 +1,6 @@
 use crate::accounts::permits::Permits;
 
-/// # Safety requirements
+/// # Safety
 /// 1. `free` must release a held mutation permit.
 pub unsafe trait Freeable: Permits {
     /// # Safety
@@ -1,13 +1,23 @@
 #![allow(unused)]
 use std::{cell::Cell, num::NonZeroU64, ops::Deref, ptr::NonNull, sync::atomic::AtomicU64};
 
-use crate::accounts::{balances::Balance, freeable::Freeable, permits::Permits};
+use crate::accounts::{
+    balances::Balance, freeable::Freeable, permits::Permits, slots::DataLocation,
+};
 
 pub mod balances;
+pub mod fairness;
 pub mod freeable;
 pub mod permits;
+pub mod slots;
+pub mod waiting;
 
-pub trait Account: Freeable + Balance {}
+pub trait Account: Freeable + Balance {
+    /// Where ralcs tracked by this account keep the pointer to their data, either
+    /// [`InAccount`](slots::InAccount) for two-word ralcs, or [`InRalc`](slots::InRalc)
+    /// for ralcs which can be coerced to unsized types.
+    type Data: DataLocation<Self>;
+}
 
 pub use private::AccPtr;
 
@@ -18,7 +28,7 @@ mod private {
 
     /// A pointer to an account allocated to guard and track a single allocation.
     #[repr(transparent)]
-    pub struct AccPtr<A: Account + ?Sized> {
+    pub struct AccPtr<A: Account> {
         /// # Safety invariant
         /// 1. ONE of these hold:
         ///    1. This is a `'static` reference.
@@ -39,7 +49,7 @@ mod private {
     unsafe impl<A: Account + Sync> Sync for AccPtr<A> {}
 
     impl<A: Account> AccPtr<A> {
-        /// # Safety requirements:
+        /// # Safety
         ///
         /// 1. ONE of the following must hold:
         ///    1. `account` is a reference with `'static` lifetime.
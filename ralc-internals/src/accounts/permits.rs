@@ -3,9 +3,11 @@ use std::{
     cell::Cell,
     num::NonZeroU32,
     sync::atomic::{AtomicU32, Ordering},
-    u64,
+    time::Instant,
 };
 
+use crate::accounts::waiting::WaitQueue;
+
 /// A container for permits to access data guarded by
 /// this account. This trait can be aither implemented in
 /// a shareable fashion using `Sync` data, or in a thread-local
@@ -43,6 +45,89 @@ pub unsafe trait Permits: Sized {
     /// Check for availablility of the mutation permit and acquire it.
     fn try_mutation(&self) -> bool;
 
+    /// Check for availability of the mutation permit and acquire it, without making way
+    /// for anybody a contention policy would favour. Disowned ralcs are dropped with
+    /// this, as nobody else will drop them if it fails for lack of contention.
+    ///
+    /// The default implementation is [`Permits::try_mutation`].
+    #[inline]
+    fn try_mutation_unfair(&self) -> bool {
+        self.try_mutation()
+    }
+
+    /// Wait for a reference permit and acquire it. Returns `false` if it is unavailable
+    /// and waiting is not supported, such as when waiting would deadlock.
+    ///
+    /// The default implementation does not wait.
+    #[inline]
+    fn lock_reference(&self) -> bool {
+        self.try_reference()
+    }
+
+    /// Wait for a reference permit like [`Permits::lock_reference`], without making way
+    /// for writers, as the current thread already holds a reference permit they wait for.
+    ///
+    /// The default implementation is [`Permits::lock_reference`].
+    #[inline]
+    fn lock_reference_recursive(&self) -> bool {
+        self.lock_reference()
+    }
+
+    /// Wait for the mutation permit and acquire it. Returns `false` if it is unavailable
+    /// and waiting is not supported, such as when waiting would deadlock.
+    ///
+    /// The default implementation does not wait.
+    #[inline]
+    fn lock_mutation(&self) -> bool {
+        self.try_mutation()
+    }
+
+    /// Wait for a reference permit until `deadline` and acquire it.
+    ///
+    /// The default implementation does not wait, and fails with [`WaitError::Unsupported`]
+    /// if the permit is unavailable.
+    #[inline]
+    fn lock_reference_until(&self, deadline: Instant) -> Result<(), WaitError> {
+        let _ = deadline;
+        if self.try_reference() {
+            Ok(())
+        } else {
+            Err(WaitError::Unsupported)
+        }
+    }
+
+    /// Wait for the mutation permit until `deadline` and acquire it.
+    ///
+    /// The default implementation does not wait, and fails with [`WaitError::Unsupported`]
+    /// if the permit is unavailable.
+    #[inline]
+    fn lock_mutation_until(&self, deadline: Instant) -> Result<(), WaitError> {
+        let _ = deadline;
+        if self.try_mutation() {
+            Ok(())
+        } else {
+            Err(WaitError::Unsupported)
+        }
+    }
+
+    /// The queue of tasks waiting for permits on this account, if it supports waiting
+    /// asynchronously. Implementations must wake it whenever a permit is relinquished.
+    #[inline]
+    fn wait_queue(&self) -> Option<&WaitQueue> {
+        None
+    }
+
+    /// Acquire an additional reference permit while already holding one. Unlike
+    /// [`Permits::try_reference`], this must not be turned away for the sake of
+    /// waiting writers, as that could deadlock.
+    ///
+    /// # Safety
+    /// 1. A reference permit must have been acquired.
+    #[inline]
+    unsafe fn duplicate_reference(&self) -> bool {
+        self.try_reference()
+    }
+
     /// Attempt to upgrade a reference permit to the mutation permit, i.e. this will
     /// only succeed if there is only a single reference permit.
     ///
@@ -50,6 +135,64 @@ pub unsafe trait Permits: Sized {
     /// 1. A reference permit must have been acquired.
     unsafe fn try_escalate(&self) -> bool;
 
+    /// Check for availability of the upgradable permit and acquire it.
+    ///
+    /// The upgradable permit grants read-only access like a reference permit, and
+    /// coexists with reference permits, but excludes the mutation permit and other
+    /// upgradable permits, so that it can later be upgraded without giving it up.
+    fn try_upgradable(&self) -> bool;
+
+    /// Wait for the upgradable permit and acquire it. Returns `false` if it is unavailable
+    /// and waiting is not supported, such as when waiting would deadlock.
+    ///
+    /// The default implementation does not wait.
+    #[inline]
+    fn lock_upgradable(&self) -> bool {
+        self.try_upgradable()
+    }
+
+    /// Attempt to upgrade the upgradable permit to the mutation permit, which only
+    /// succeeds if no reference permits are held.
+    ///
+    /// # Safety
+    /// 1. The upgradable permit must have been acquired.
+    unsafe fn try_upgrade(&self) -> bool;
+
+    /// Wait for all reference permits to be relinquished, then upgrade the upgradable
+    /// permit to the mutation permit. Returns `false` if reference permits are held
+    /// and waiting is not supported.
+    ///
+    /// The default implementation does not wait.
+    ///
+    /// # Safety
+    /// 1. The upgradable permit must have been acquired.
+    #[inline]
+    unsafe fn upgrade(&self) -> bool {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.try_upgrade()
+        }
+    }
+
+    /// Downgrade the upgradable permit to a reference permit.
+    ///
+    /// # Safety
+    /// 1. The upgradable permit must have been acquired.
+    unsafe fn downgrade_upgradable(&self);
+
+    /// Downgrade the mutation permit to the upgradable permit.
+    ///
+    /// # Safety
+    /// 1. The mutation permit must have been acquired.
+    unsafe fn relax_to_upgradable(&self);
+
+    /// Relinquish the upgradable permit.
+    ///
+    /// # Safety
+    /// 1. The upgradable permit must have been acquired.
+    unsafe fn abandon_upgradable(&self);
+
     /// Downgrade existing mutation permit to reference permit.
     ///
     /// # Safety
@@ -58,17 +201,51 @@ pub unsafe trait Permits: Sized {
 
     /// Relinquish a reference permit.
     ///
-    /// # Safety requirements:
+    /// # Safety
     /// 1. A reference permit must have been acquired.
     unsafe fn abandon_reference(&self);
 
+    /// Relinquish a reference permit, or turn it into the mutation permit if no other
+    /// permit is held. Returns whether the mutation permit is now held.
+    ///
+    /// Implementations may instead relinquish the permit and then try to acquire the
+    /// mutation permit. Either way, of several permits relinquished like this, the last
+    /// one must end up as the mutation permit unless another permit was acquired meanwhile.
+    ///
+    /// # Safety
+    /// 1. A reference permit must have been acquired.
+    unsafe fn abandon_reference_or_escalate(&self) -> bool;
+
+    /// Relinquish the upgradable permit, or turn it into the mutation permit if no other
+    /// permit is held, like [`Permits::abandon_reference_or_escalate`].
+    ///
+    /// # Safety
+    /// 1. The upgradable permit must have been acquired.
+    unsafe fn abandon_upgradable_or_upgrade(&self) -> bool;
+
     /// Relinquish the mutation permit.
     ///
-    /// # Safety requirements:
+    /// # Safety
     /// 1. The mutation permit must have been acquired.
     unsafe fn abandon_mutation(&self);
 }
 
+/// Why a permit could not be acquired by a deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WaitError {
+    /// The permit was still unavailable at the deadline.
+    TimedOut,
+    /// The permit was unavailable, and these permits do not support waiting.
+    Unsupported,
+}
+
+/// Flag set in counting permits while the upgradable permit is held. The
+/// remaining bits count reference permits, and all bits set is the mutation permit.
+const UPGRADABLE: u32 = 1 << 31;
+
+/// The most reference permits counting permits can hand out.
+const MAX_REFERENCES: u32 = !UPGRADABLE - 1;
+
 unsafe impl Permits for Cell<u32> {
     type UnderlyingLockableEntity = ();
 
@@ -79,7 +256,7 @@ unsafe impl Permits for Cell<u32> {
 
     #[inline]
     fn try_reference(&self) -> bool {
-        if self.get() < u32::MAX {
+        if self.get() & !UPGRADABLE < MAX_REFERENCES {
             self.update(|n| n + 1);
             true
         } else {
@@ -107,6 +284,42 @@ unsafe impl Permits for Cell<u32> {
         }
     }
 
+    #[inline]
+    fn try_upgradable(&self) -> bool {
+        let n = self.get();
+        if n & UPGRADABLE == 0 {
+            self.set(n | UPGRADABLE);
+            true
+        } else {
+            false
+        }
+    }
+
+    #[inline]
+    unsafe fn try_upgrade(&self) -> bool {
+        if self.get() == UPGRADABLE {
+            self.set(u32::MAX);
+            true
+        } else {
+            false
+        }
+    }
+
+    #[inline]
+    unsafe fn downgrade_upgradable(&self) {
+        self.update(|n| (n & !UPGRADABLE) + 1)
+    }
+
+    #[inline]
+    unsafe fn relax_to_upgradable(&self) {
+        self.set(UPGRADABLE)
+    }
+
+    #[inline]
+    unsafe fn abandon_upgradable(&self) {
+        self.update(|n| n & !UPGRADABLE)
+    }
+
     #[inline]
     unsafe fn relax_permit(&self) {
         self.set(1)
@@ -117,6 +330,28 @@ unsafe impl Permits for Cell<u32> {
         self.update(|n| n - 1)
     }
 
+    #[inline]
+    unsafe fn abandon_reference_or_escalate(&self) -> bool {
+        if self.get() == 1 {
+            self.set(u32::MAX);
+            true
+        } else {
+            self.update(|n| n - 1);
+            false
+        }
+    }
+
+    #[inline]
+    unsafe fn abandon_upgradable_or_upgrade(&self) -> bool {
+        if self.get() == UPGRADABLE {
+            self.set(u32::MAX);
+            true
+        } else {
+            self.update(|n| n & !UPGRADABLE);
+            false
+        }
+    }
+
     #[inline]
     unsafe fn abandon_mutation(&self) {
         self.set(0)
@@ -131,8 +366,10 @@ unsafe impl Permits for AtomicU32 {
     }
 
     fn try_reference(&self) -> bool {
-        self.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_add(1))
-            .is_ok()
+        self.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+            (n & !UPGRADABLE < MAX_REFERENCES).then(|| n + 1)
+        })
+        .is_ok()
     }
 
     fn try_mutation(&self) -> bool {
@@ -145,6 +382,31 @@ unsafe impl Permits for AtomicU32 {
             .is_ok()
     }
 
+    fn try_upgradable(&self) -> bool {
+        self.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+            (n & UPGRADABLE == 0).then_some(n | UPGRADABLE)
+        })
+        .is_ok()
+    }
+
+    unsafe fn try_upgrade(&self) -> bool {
+        self.compare_exchange(UPGRADABLE, u32::MAX, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    unsafe fn downgrade_upgradable(&self) {
+        // Readers may come and go meanwhile, but nobody else touches the flag.
+        self.fetch_sub(UPGRADABLE - 1, Ordering::Release);
+    }
+
+    unsafe fn relax_to_upgradable(&self) {
+        self.store(UPGRADABLE, Ordering::Release);
+    }
+
+    unsafe fn abandon_upgradable(&self) {
+        self.fetch_and(!UPGRADABLE, Ordering::Release);
+    }
+
     unsafe fn relax_permit(&self) {
         self.store(1, Ordering::Release);
     }
@@ -153,6 +415,24 @@ unsafe impl Permits for AtomicU32 {
         self.fetch_sub(1, Ordering::Release);
     }
 
+    unsafe fn abandon_reference_or_escalate(&self) -> bool {
+        let previous = self.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+            Some(if n == 1 { u32::MAX } else { n - 1 })
+        });
+        previous == Ok(1)
+    }
+
+    unsafe fn abandon_upgradable_or_upgrade(&self) -> bool {
+        let previous = self.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+            Some(if n == UPGRADABLE {
+                u32::MAX
+            } else {
+                n & !UPGRADABLE
+            })
+        });
+        previous == Ok(UPGRADABLE)
+    }
+
     unsafe fn abandon_mutation(&self) {
         self.store(0, Ordering::Release);
     }
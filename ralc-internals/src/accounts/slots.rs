@@ -0,0 +1,204 @@
+use std::{
+    cell::Cell,
+    marker::PhantomData,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+/// Where the pointer to the data an account tracks is kept, as chosen by
+/// [`Account::Data`](crate::accounts::Account::Data).
+///
+/// # Safety
+/// 1. `data` must return the pointer passed to `track` for as long as the account tracks
+///    that data, and the pointer passed to `recall` likewise
+pub unsafe trait DataLocation<A: ?Sized> {
+    /// What a ralc keeps in place of the pointer.
+    type Ptr<P: ?Sized>: Copy + Unpin;
+
+    /// Keep the pointer to data which `account` starts tracking.
+    ///
+    /// # Safety
+    /// 1. No permits may be held on `account`, and it must not track other data
+    unsafe fn track<P: ?Sized>(account: &A, data: NonNull<P>) -> Self::Ptr<P>;
+
+    /// Refer to data which `account` already tracks, or has tracked.
+    fn recall<P: ?Sized>(data: NonNull<P>) -> Self::Ptr<P>;
+
+    /// The pointer to the data, or to data `account` tracked since if it no longer tracks
+    /// the data `ptr` refers to.
+    ///
+    /// # Safety
+    /// 1. Unless `P` is sized, `account` must still track the data `ptr` refers to
+    unsafe fn data<P: ?Sized>(account: &A, ptr: Self::Ptr<P>) -> NonNull<P>;
+}
+
+/// The pointer is kept in every ralc, which works for any data.
+pub struct InRalc;
+
+// SAFETY:
+// 1. The pointer is kept as is
+unsafe impl<A: ?Sized> DataLocation<A> for InRalc {
+    type Ptr<P: ?Sized> = NonNull<P>;
+
+    #[inline]
+    unsafe fn track<P: ?Sized>(_account: &A, data: NonNull<P>) -> NonNull<P> {
+        data
+    }
+
+    #[inline]
+    fn recall<P: ?Sized>(data: NonNull<P>) -> NonNull<P> {
+        data
+    }
+
+    #[inline]
+    unsafe fn data<P: ?Sized>(_account: &A, ptr: NonNull<P>) -> NonNull<P> {
+        ptr
+    }
+}
+
+/// The pointer is kept once in the [`DataSlot`] of the account, so that ralcs are only two
+/// words.
+///
+/// Ralcs of such accounts cannot be coerced to unsized types, as the pointer in the account
+/// would lack the metadata.
+pub struct InAccount;
+
+// SAFETY:
+// 1. Guaranteed by `DataSlot`, and the pointer is only replaced for newly tracked data
+unsafe impl<A: DataSlot + ?Sized> DataLocation<A> for InAccount {
+    type Ptr<P: ?Sized> = PhantomData<NonNull<P>>;
+
+    #[inline]
+    unsafe fn track<P: ?Sized>(account: &A, data: NonNull<P>) -> Self::Ptr<P> {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            account.set_data(ErasedPtr::new(data));
+        }
+        PhantomData
+    }
+
+    #[inline]
+    fn recall<P: ?Sized>(_data: NonNull<P>) -> Self::Ptr<P> {
+        PhantomData
+    }
+
+    #[inline]
+    unsafe fn data<P: ?Sized>(account: &A, _ptr: Self::Ptr<P>) -> NonNull<P> {
+        unsafe {
+            // SAFETY:
+            // 1. Set by `track` from a `NonNull<P>` while the account tracks the data, or
+            //    from any pointer since, which only differs in its metadata, as guaranteed
+            //    by caller
+            account.data().get()
+        }
+    }
+}
+
+/// A pointer of at most two words with its type erased, as kept in a [`DataSlot`].
+#[derive(Clone, Copy)]
+pub struct ErasedPtr([*mut u8; 2]);
+
+impl ErasedPtr {
+    /// A pointer for a [`DataSlot`] to hold before any data is tracked.
+    pub const DANGLING: Self = Self([ptr::dangling_mut(), ptr::null_mut()]);
+
+    /// Erase the type of `ptr`.
+    #[inline]
+    pub fn new<P: ?Sized>(ptr: NonNull<P>) -> Self {
+        const {
+            assert!(
+                size_of::<NonNull<P>>() <= size_of::<Self>(),
+                "data kept in accounts must have a pointer of at most two words"
+            )
+        };
+        let mut words = Self::DANGLING.0;
+        unsafe {
+            // SAFETY:
+            // 1. Fits as asserted above, and pointers are aligned like pointer words
+            words.as_mut_ptr().cast::<NonNull<P>>().write(ptr);
+        }
+        Self(words)
+    }
+
+    /// Restore the pointer.
+    ///
+    /// # Safety
+    /// 1. This must have been made from a `NonNull<P>`, or from a pointer to a type with
+    ///    the same metadata, such as any sized type
+    #[inline]
+    pub unsafe fn get<P: ?Sized>(self) -> NonNull<P> {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller, so that the words hold a valid `NonNull<P>`
+            self.0.as_ptr().cast::<NonNull<P>>().read()
+        }
+    }
+}
+
+/// A type-erased place in an account for the pointer to the data it tracks, for
+/// accounts using [`InAccount`].
+///
+/// `Cell<ErasedPtr>` is a slot for accounts which are not shared between threads, and
+/// [`AtomicErasedPtr`] one for accounts which are.
+///
+/// # Safety
+/// 1. `data` must return the pointer last passed to `set_data`
+pub unsafe trait DataSlot {
+    /// Keep the pointer to newly tracked data.
+    ///
+    /// # Safety
+    /// 1. No permits may be held on the account, and it must not track other data
+    unsafe fn set_data(&self, data: ErasedPtr);
+
+    /// The pointer last passed to `set_data`, or any pointer before the first call.
+    fn data(&self) -> ErasedPtr;
+}
+
+// SAFETY:
+// 1. The pointer is kept as is
+unsafe impl DataSlot for Cell<ErasedPtr> {
+    #[inline]
+    unsafe fn set_data(&self, data: ErasedPtr) {
+        self.set(data);
+    }
+
+    #[inline]
+    fn data(&self) -> ErasedPtr {
+        self.get()
+    }
+}
+
+/// An [`ErasedPtr`] which can be shared between threads.
+pub struct AtomicErasedPtr([AtomicPtr<u8>; 2]);
+
+impl AtomicErasedPtr {
+    pub const fn new() -> Self {
+        let [address, metadata] = ErasedPtr::DANGLING.0;
+        Self([AtomicPtr::new(address), AtomicPtr::new(metadata)])
+    }
+}
+
+impl Default for AtomicErasedPtr {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// SAFETY:
+// 1. The pointer is kept as is. Ralcs only read it once they reach the account, and so
+//    after it was set, while stale pointers may see a torn pointer, which `InAccount`
+//    only allows them for sized data, whose pointer is the first word alone
+unsafe impl DataSlot for AtomicErasedPtr {
+    #[inline]
+    unsafe fn set_data(&self, data: ErasedPtr) {
+        for (word, value) in self.0.iter().zip(data.0) {
+            word.store(value, Ordering::Relaxed);
+        }
+    }
+
+    #[inline]
+    fn data(&self) -> ErasedPtr {
+        ErasedPtr(self.0.each_ref().map(|word| word.load(Ordering::Relaxed)))
+    }
+}
@@ -0,0 +1,310 @@
+use std::{
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering, fence},
+    },
+    task::Waker,
+    time::Instant,
+};
+
+use crate::accounts::permits::{Permits, WaitError};
+
+/// A queue of tasks waiting for permits to become available.
+///
+/// Every registered waker is woken whenever any permit is relinquished, so a task which
+/// is cancelled after being woken never swallows a wakeup meant for another.
+#[derive(Default)]
+pub struct WaitQueue {
+    waiting: AtomicUsize,
+    waiters: Mutex<Waiters>,
+}
+
+#[derive(Default)]
+struct Waiters {
+    next_key: u64,
+    wakers: Vec<(u64, Waker)>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self {
+            waiting: AtomicUsize::new(0),
+            waiters: Mutex::new(Waiters {
+                next_key: 0,
+                wakers: Vec::new(),
+            }),
+        }
+    }
+
+    /// Register `waker` to be woken once permits are relinquished, or update it if `key`
+    /// is still registered.
+    ///
+    /// Permits must be retried after registering, as they may have been relinquished just before.
+    pub fn register(&self, key: &mut Option<u64>, waker: &Waker) {
+        let mut waiters = self.waiters.lock().unwrap_or_else(|e| e.into_inner());
+
+        let existing = key.and_then(|key| waiters.wakers.iter_mut().find(|(k, _)| *k == key));
+        match existing {
+            Some((_, registered)) => registered.clone_from(waker),
+            None => {
+                let new = waiters.next_key;
+                waiters.next_key += 1;
+                waiters.wakers.push((new, waker.clone()));
+                *key = Some(new);
+            }
+        }
+
+        self.waiting.store(waiters.wakers.len(), Ordering::Relaxed);
+        // Pairs with the fence in `wake_all`, so that either the retry sees the permits
+        // relinquished, or the relinquishing thread sees this waker.
+        fence(Ordering::SeqCst);
+    }
+
+    /// Remove the waker registered under `key`, if any.
+    pub fn deregister(&self, key: &mut Option<u64>) {
+        let Some(key) = key.take() else {
+            return;
+        };
+        if self.waiting.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+
+        let mut waiters = self.waiters.lock().unwrap_or_else(|e| e.into_inner());
+        waiters.wakers.retain(|(k, _)| *k != key);
+        self.waiting.store(waiters.wakers.len(), Ordering::Relaxed);
+    }
+
+    /// Wake and remove all registered wakers.
+    pub fn wake_all(&self) {
+        fence(Ordering::SeqCst);
+        if self.waiting.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+
+        let wakers = {
+            let mut waiters = self.waiters.lock().unwrap_or_else(|e| e.into_inner());
+            self.waiting.store(0, Ordering::Relaxed);
+            std::mem::take(&mut waiters.wakers)
+        };
+        for (_, waker) in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Number of registered wakers.
+    pub fn len(&self) -> usize {
+        self.waiting.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// [`Permits`] with a [`WaitQueue`], which wakes waiting tasks whenever a permit is relinquished.
+#[derive(Default)]
+pub struct Waitable<P: Permits> {
+    permits: P,
+    queue: WaitQueue,
+}
+
+impl<P: Permits> Waitable<P> {
+    pub const fn new(permits: P) -> Self {
+        Self {
+            permits,
+            queue: WaitQueue::new(),
+        }
+    }
+}
+
+// SAFETY:
+// 1. Delegated implementation, which only adds wakeups
+unsafe impl<P: Permits> Permits for Waitable<P> {
+    type UnderlyingLockableEntity = P::UnderlyingLockableEntity;
+
+    #[inline]
+    unsafe fn underlying(&self) -> &Self::UnderlyingLockableEntity {
+        unsafe {
+            // SAFETY:
+            // Delegated responsibility
+            self.permits.underlying()
+        }
+    }
+
+    #[inline]
+    fn try_reference(&self) -> bool {
+        self.permits.try_reference()
+    }
+
+    #[inline]
+    fn try_mutation(&self) -> bool {
+        self.permits.try_mutation()
+    }
+
+    #[inline]
+    fn try_mutation_unfair(&self) -> bool {
+        self.permits.try_mutation_unfair()
+    }
+
+    #[inline]
+    fn lock_reference(&self) -> bool {
+        self.permits.lock_reference()
+    }
+
+    #[inline]
+    fn lock_reference_recursive(&self) -> bool {
+        self.permits.lock_reference_recursive()
+    }
+
+    #[inline]
+    fn lock_mutation(&self) -> bool {
+        self.permits.lock_mutation()
+    }
+
+    #[inline]
+    fn lock_reference_until(&self, deadline: Instant) -> Result<(), WaitError> {
+        self.permits.lock_reference_until(deadline)
+    }
+
+    #[inline]
+    fn lock_mutation_until(&self, deadline: Instant) -> Result<(), WaitError> {
+        self.permits.lock_mutation_until(deadline)
+    }
+
+    #[inline]
+    fn wait_queue(&self) -> Option<&WaitQueue> {
+        Some(&self.queue)
+    }
+
+    #[inline]
+    fn try_upgradable(&self) -> bool {
+        self.permits.try_upgradable()
+    }
+
+    #[inline]
+    fn lock_upgradable(&self) -> bool {
+        self.permits.lock_upgradable()
+    }
+
+    #[inline]
+    unsafe fn try_upgrade(&self) -> bool {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.permits.try_upgrade()
+        }
+    }
+
+    #[inline]
+    unsafe fn upgrade(&self) -> bool {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.permits.upgrade()
+        }
+    }
+
+    #[inline]
+    unsafe fn downgrade_upgradable(&self) {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.permits.downgrade_upgradable();
+        }
+        self.queue.wake_all();
+    }
+
+    #[inline]
+    unsafe fn relax_to_upgradable(&self) {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.permits.relax_to_upgradable();
+        }
+        self.queue.wake_all();
+    }
+
+    #[inline]
+    unsafe fn abandon_upgradable(&self) {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.permits.abandon_upgradable();
+        }
+        self.queue.wake_all();
+    }
+
+    #[inline]
+    unsafe fn duplicate_reference(&self) -> bool {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.permits.duplicate_reference()
+        }
+    }
+
+    #[inline]
+    unsafe fn try_escalate(&self) -> bool {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.permits.try_escalate()
+        }
+    }
+
+    #[inline]
+    unsafe fn relax_permit(&self) {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.permits.relax_permit();
+        }
+        self.queue.wake_all();
+    }
+
+    #[inline]
+    unsafe fn abandon_reference(&self) {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.permits.abandon_reference();
+        }
+        self.queue.wake_all();
+    }
+
+    #[inline]
+    unsafe fn abandon_reference_or_escalate(&self) -> bool {
+        let escalated = unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.permits.abandon_reference_or_escalate()
+        };
+        if !escalated {
+            self.queue.wake_all();
+        }
+        escalated
+    }
+
+    #[inline]
+    unsafe fn abandon_upgradable_or_upgrade(&self) -> bool {
+        let upgraded = unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.permits.abandon_upgradable_or_upgrade()
+        };
+        if !upgraded {
+            self.queue.wake_all();
+        }
+        upgraded
+    }
+
+    #[inline]
+    unsafe fn abandon_mutation(&self) {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.permits.abandon_mutation();
+        }
+        self.queue.wake_all();
+    }
+}
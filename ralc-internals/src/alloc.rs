@@ -0,0 +1,429 @@
+use std::{
+    alloc::{Layout, handle_alloc_error},
+    cell::RefCell,
+    mem::offset_of,
+    ptr::NonNull,
+};
+
+/// An allocator for the data of ralcs, such as an arena.
+///
+/// This is the part of the unstable `std::alloc::Allocator` which ralcs need. With the
+/// `nightly` feature, it is implemented for every such allocator.
+///
+/// # Safety
+/// 1. Memory returned by `allocate` must stay valid until passed to `deallocate`, even
+///    if the allocator is moved
+pub unsafe trait Allocator {
+    /// Allocate memory fitting `layout`, which never has a size of zero. Returns `None`
+    /// if the memory is exhausted.
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>>;
+
+    /// # Safety
+    /// 1. `ptr` must have been allocated by this allocator with the same `layout`
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+}
+
+/// The global allocator, as used by [`Box`].
+///
+/// With the `recycling` feature, memory freed on a thread is kept for reuse like with
+/// [`Recycling`], but by exact layout, so that payloads can still be taken over by a
+/// [`Box`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Global;
+
+// SAFETY:
+// 1. Memory of the global allocator is valid until deallocated, and memory kept for
+//    reuse is handed out at most once
+unsafe impl Allocator for Global {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        #[cfg(feature = "recycling")]
+        if let Some(ptr) = Recycler::reuse(layout) {
+            return Some(ptr);
+        }
+        allocate_global(layout)
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        #[cfg(feature = "recycling")]
+        if Recycler::keep(ptr, layout) {
+            return;
+        }
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            deallocate_global(ptr, layout);
+        }
+    }
+}
+
+#[inline]
+fn allocate_global(layout: Layout) -> Option<NonNull<u8>> {
+    NonNull::new(unsafe {
+        // SAFETY:
+        // 1. Layouts never have a size of zero
+        std::alloc::alloc(layout)
+    })
+}
+
+/// # Safety
+/// 1. `ptr` must have been allocated by the global allocator with the same `layout`
+#[inline]
+unsafe fn deallocate_global(ptr: NonNull<u8>, layout: Layout) {
+    unsafe {
+        // SAFETY:
+        // 1. Guaranteed by caller
+        std::alloc::dealloc(ptr.as_ptr(), layout);
+    }
+}
+
+// SAFETY:
+// 1. Guaranteed by `std::alloc::Allocator`
+#[cfg(feature = "nightly")]
+unsafe impl<A: std::alloc::Allocator> Allocator for A {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        std::alloc::Allocator::allocate(self, layout)
+            .ok()
+            .map(NonNull::cast)
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            std::alloc::Allocator::deallocate(self, ptr, layout);
+        }
+    }
+}
+
+/// An allocator for payloads kept in place, such as inline next to their account.
+///
+/// It never allocates, and deallocating is left to the owner of the place.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct InPlace;
+
+// SAFETY:
+// 1. Nothing is ever allocated
+unsafe impl Allocator for InPlace {
+    #[inline]
+    fn allocate(&self, _layout: Layout) -> Option<NonNull<u8>> {
+        None
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {}
+}
+
+/// The global allocator, except that memory freed on a thread is kept for reuse by
+/// allocations of the same size class on that thread.
+///
+/// Size classes are four per power of two, so that similar types share memory. Memory is
+/// kept by layout rather than by type, as memory of one layout serves any type of it,
+/// which in practice are the few types allocated over and over.
+///
+/// Each thread keeps at most [`Recycling::set_limit`] bytes, by default 1 MiB, and
+/// releases them when it exits or calls [`Recycling::trim`]. Memory freed by [`Global`]
+/// is kept likewise with the `recycling` feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Recycling;
+
+thread_local! {
+    static RECYCLER: RefCell<Recycler> = const {
+        RefCell::new(Recycler {
+            limit: 1 << 20,
+            cached: 0,
+            classes: Vec::new(),
+        })
+    };
+}
+
+/// Memory kept for reuse, by the layout it was allocated from the global allocator with.
+struct Recycler {
+    limit: usize,
+    cached: usize,
+    classes: Vec<(Layout, Vec<NonNull<u8>>)>,
+}
+
+impl Recycler {
+    fn class(&mut self, layout: Layout) -> &mut Vec<NonNull<u8>> {
+        let index = match self.classes.iter().position(|(l, _)| *l == layout) {
+            Some(index) => index,
+            None => {
+                self.classes.push((layout, Vec::new()));
+                self.classes.len() - 1
+            }
+        };
+        &mut self.classes[index].1
+    }
+
+    /// Take memory kept for `layout`, if any.
+    fn reuse(layout: Layout) -> Option<NonNull<u8>> {
+        RECYCLER
+            .try_with(|recycler| {
+                let mut recycler = recycler.borrow_mut();
+                let (_, free) = recycler.classes.iter_mut().find(|(l, _)| *l == layout)?;
+                let ptr = free.pop()?;
+                recycler.cached -= layout.size();
+                Some(ptr)
+            })
+            .ok()
+            .flatten()
+    }
+
+    /// Keep memory allocated from the global allocator with `layout`, unless that would
+    /// exceed the limit. Threads which have torn down their recycler keep nothing.
+    fn keep(ptr: NonNull<u8>, layout: Layout) -> bool {
+        RECYCLER
+            .try_with(|recycler| {
+                let mut recycler = recycler.borrow_mut();
+                if recycler.cached + layout.size() > recycler.limit {
+                    return false;
+                }
+                recycler.cached += layout.size();
+                recycler.class(layout).push(ptr);
+                true
+            })
+            .unwrap_or(false)
+    }
+
+    /// Release memory until at most `limit` bytes are kept.
+    fn trim_to(&mut self, limit: usize) {
+        for (layout, free) in &mut self.classes {
+            while self.cached > limit
+                && let Some(ptr) = free.pop()
+            {
+                self.cached -= layout.size();
+                unsafe {
+                    // SAFETY:
+                    // 1. Only memory from the global allocator is kept, by its layout
+                    deallocate_global(ptr, *layout);
+                }
+            }
+        }
+        self.classes.retain(|(_, free)| !free.is_empty());
+    }
+}
+
+impl Drop for Recycler {
+    fn drop(&mut self) {
+        self.trim_to(0);
+    }
+}
+
+impl Recycling {
+    /// Set the number of bytes the current thread keeps for reuse, releasing any excess.
+    pub fn set_limit(bytes: usize) {
+        RECYCLER.with_borrow_mut(|recycler| {
+            recycler.limit = bytes;
+            recycler.trim_to(bytes);
+        })
+    }
+
+    /// Number of bytes the current thread keeps for reuse.
+    pub fn cached() -> usize {
+        RECYCLER.with_borrow(|recycler| recycler.cached)
+    }
+
+    /// Release all memory the current thread keeps for reuse.
+    pub fn trim() {
+        RECYCLER.with_borrow_mut(|recycler| recycler.trim_to(0))
+    }
+
+    /// The layout of the size class `layout` falls into.
+    #[inline]
+    fn size_class(layout: Layout) -> Layout {
+        let step = (layout.size().next_power_of_two() / 4).max(layout.align());
+        Layout::from_size_align(layout.size().next_multiple_of(step), layout.align())
+            .unwrap_or(layout)
+    }
+}
+
+// SAFETY:
+// 1. Memory of the global allocator is valid until deallocated, and memory kept for
+//    reuse is handed out at most once. Memory is allocated and kept by the layout of its
+//    size class, which fits `layout`
+unsafe impl Allocator for Recycling {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let class = Self::size_class(layout);
+        Recycler::reuse(class).or_else(|| allocate_global(class))
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let class = Self::size_class(layout);
+        if !Recycler::keep(ptr, class) {
+            unsafe {
+                // SAFETY:
+                // 1. Guaranteed by caller, as all memory comes from the global allocator
+                //    with the layout of its size class
+                deallocate_global(ptr, class);
+            }
+        }
+    }
+}
+
+/// The data of a ralc, allocated together with the allocator which frees it, so that
+/// whichever pointer drops it last can do so.
+///
+/// With the zero-sized [`Global`] allocator, this has the same layout as the data alone,
+/// so that a [`Box`] can be adopted as is.
+#[repr(C)]
+pub struct Payload<Alloc, T: ?Sized> {
+    alloc: Alloc,
+    data: T,
+}
+
+impl<Alloc: Allocator, T> Payload<Alloc, T> {
+    /// Move `data` into a new payload allocated by `alloc`.
+    pub fn new_in(data: T, alloc: Alloc) -> NonNull<Self> {
+        let layout = Layout::new::<Self>();
+        let ptr = if layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            alloc
+                .allocate(layout)
+                .unwrap_or_else(|| handle_alloc_error(layout))
+                .cast()
+        };
+
+        unsafe {
+            // SAFETY:
+            // 1. Freshly allocated for this layout
+            ptr.write(Payload { alloc, data });
+        }
+        ptr
+    }
+
+    /// Move `data` into a payload in an existing place.
+    ///
+    /// # Safety
+    /// 1. `place` must be valid for writes, and must not hold a live payload
+    #[inline]
+    pub unsafe fn write(place: NonNull<Self>, data: T, alloc: Alloc) {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            place.write(Payload { alloc, data });
+        }
+    }
+
+    /// The pointer to the data in a payload, which need not be live.
+    ///
+    /// # Safety
+    /// 1. `this` must point to a payload, or to where one was
+    #[inline]
+    pub unsafe fn data_ptr(this: NonNull<Self>) -> NonNull<T> {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller, so that the offset stays within the payload and
+            //    does not wrap around to null
+            NonNull::new_unchecked(this.as_ptr().wrapping_byte_add(offset_of!(Self, data))).cast()
+        }
+    }
+
+    /// The payload holding the data `data` points to, undoing [`Payload::data_ptr`].
+    ///
+    /// # Safety
+    /// 1. `data` must point to the data in a payload, or to where it was
+    #[inline]
+    pub unsafe fn from_data_ptr(data: NonNull<T>) -> NonNull<Self> {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller, so that the offset stays within the payload and
+            //    does not wrap around to null
+            NonNull::new_unchecked(data.as_ptr().wrapping_byte_sub(offset_of!(Self, data))).cast()
+        }
+    }
+
+    /// Move the data out of a payload and deallocate it.
+    ///
+    /// # Safety
+    /// 1. `this` must be a live payload, which is not used again
+    pub unsafe fn into_inner(this: NonNull<Self>) -> T {
+        let Payload { alloc, data } = unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            this.read()
+        };
+
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            Self::deallocate(this, &alloc, Layout::new::<Self>());
+        }
+        data
+    }
+}
+
+impl<T: ?Sized> Payload<Global, T> {
+    /// Adopt the allocation of a box as a payload.
+    pub fn from_box(data: Box<T>) -> NonNull<Self> {
+        unsafe {
+            // SAFETY:
+            // 1. Boxes are never null
+            // 2. The layouts match, as `Global` is zero-sized
+            NonNull::new_unchecked(Box::into_raw(data) as *mut Self)
+        }
+    }
+
+    /// Give up a payload as a box.
+    ///
+    /// # Safety
+    /// 1. `this` must be a live payload, which is not used again
+    pub unsafe fn into_box(this: NonNull<Self>) -> Box<T> {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            // 2. The layouts match, as `Global` is zero-sized
+            Box::from_raw(this.as_ptr() as *mut T)
+        }
+    }
+}
+
+impl<Alloc: Allocator, T: ?Sized> Payload<Alloc, T> {
+    /// The pointer to the data in a payload.
+    ///
+    /// # Safety
+    /// 1. `this` must be a live payload
+    #[inline]
+    pub unsafe fn data(this: NonNull<Self>) -> NonNull<T> {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller, and no reference is created
+            NonNull::new_unchecked(&raw mut (*this.as_ptr()).data)
+        }
+    }
+
+    /// Drop the data of a payload and deallocate it.
+    ///
+    /// # Safety
+    /// 1. `this` must be a live payload, which is not used again
+    pub unsafe fn free(this: NonNull<Self>) {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            // 2. The allocator is moved out before its memory is released
+            let layout = Layout::for_value(this.as_ref());
+            let alloc = (&raw const (*this.as_ptr()).alloc).read();
+            Self::data(this).drop_in_place();
+            Self::deallocate(this, &alloc, layout);
+        }
+    }
+
+    /// # Safety
+    /// 1. `this` must have been allocated by `alloc` with `layout`, and its data dropped
+    ///    or moved out
+    unsafe fn deallocate(this: NonNull<Self>, alloc: &Alloc, layout: Layout) {
+        if layout.size() != 0 {
+            unsafe {
+                // SAFETY:
+                // 1. Guaranteed by caller
+                alloc.deallocate(this.cast(), layout);
+            }
+        }
+    }
+}
@@ -10,76 +10,226 @@ pub trait DelegateAccountImpl {
     fn permits(&self) -> &Self::DelegatedPermits;
 }
 
+/// Implement [`Balance`] and [`Permits`] for a [`DelegateAccountImpl`] type.
+///
+/// Generic types take their impl parameters in brackets, as in
+/// `delegate_account_impl!([B: Balance, P: Permits] MyAccount<B, P>)`.
 #[macro_export]
 macro_rules! delegate_account_impl {
-    ($delegator:ty) => {
-        trait CheckDelegateAccountImpl: DelegateAccountImpl {}
-        impl CheckDelegateAccountImpl for $delegator {}
-
+    ([$($generics:tt)*] $delegator:ty) => {
         // SAFETY:
         // 1. delegated implementation.
-        unsafe impl Balance for $delegator {
+        unsafe impl<$($generics)*> $crate::accounts::balances::Balance for $delegator {
+            #[inline]
             fn invalidate(&self) {
-                self.balance().invalidate();
+                $crate::accounts::balances::Balance::invalidate($crate::delegate_impl::DelegateAccountImpl::balance(self));
             }
 
+            #[inline]
+            fn exhausted(&self) -> bool {
+                $crate::accounts::balances::Balance::exhausted($crate::delegate_impl::DelegateAccountImpl::balance(self))
+            }
+
+            #[inline]
             fn check(&self) -> u64 {
-                self.balance().check()
+                $crate::accounts::balances::Balance::check($crate::delegate_impl::DelegateAccountImpl::balance(self))
+            }
+
+            #[inline]
+            fn poison(&self) {
+                $crate::accounts::balances::Balance::poison($crate::delegate_impl::DelegateAccountImpl::balance(self))
+            }
+
+            #[inline]
+            fn is_poisoned(&self) -> bool {
+                $crate::accounts::balances::Balance::is_poisoned($crate::delegate_impl::DelegateAccountImpl::balance(self))
+            }
+
+            #[inline]
+            fn clear_poison(&self) {
+                $crate::accounts::balances::Balance::clear_poison($crate::delegate_impl::DelegateAccountImpl::balance(self))
             }
         }
 
         // SAFETY:
         // 1. delegated implementation.
-        unsafe impl<B: Balance, P: Permits> Permits for $delegator {
+        unsafe impl<$($generics)*> $crate::accounts::permits::Permits for $delegator {
+            #[inline]
             fn try_reference(&self) -> bool {
-                self.permits().try_reference()
+                $crate::accounts::permits::Permits::try_reference($crate::delegate_impl::DelegateAccountImpl::permits(self))
             }
 
+            #[inline]
             fn try_mutation(&self) -> bool {
-                self.permits().try_mutation()
+                $crate::accounts::permits::Permits::try_mutation($crate::delegate_impl::DelegateAccountImpl::permits(self))
+            }
+
+            #[inline]
+            fn try_mutation_unfair(&self) -> bool {
+                $crate::accounts::permits::Permits::try_mutation_unfair($crate::delegate_impl::DelegateAccountImpl::permits(self))
+            }
+
+            #[inline]
+            fn lock_reference(&self) -> bool {
+                $crate::accounts::permits::Permits::lock_reference($crate::delegate_impl::DelegateAccountImpl::permits(self))
+            }
+
+            #[inline]
+            fn lock_reference_recursive(&self) -> bool {
+                $crate::accounts::permits::Permits::lock_reference_recursive($crate::delegate_impl::DelegateAccountImpl::permits(self))
+            }
+
+            #[inline]
+            fn lock_mutation(&self) -> bool {
+                $crate::accounts::permits::Permits::lock_mutation($crate::delegate_impl::DelegateAccountImpl::permits(self))
+            }
+
+            #[inline]
+            fn lock_reference_until(&self, deadline: ::std::time::Instant) -> ::core::result::Result<(), $crate::accounts::permits::WaitError> {
+                $crate::accounts::permits::Permits::lock_reference_until($crate::delegate_impl::DelegateAccountImpl::permits(self), deadline)
+            }
+
+            #[inline]
+            fn lock_mutation_until(&self, deadline: ::std::time::Instant) -> ::core::result::Result<(), $crate::accounts::permits::WaitError> {
+                $crate::accounts::permits::Permits::lock_mutation_until($crate::delegate_impl::DelegateAccountImpl::permits(self), deadline)
+            }
+
+            #[inline]
+            fn wait_queue(&self) -> ::core::option::Option<&$crate::accounts::waiting::WaitQueue> {
+                $crate::accounts::permits::Permits::wait_queue($crate::delegate_impl::DelegateAccountImpl::permits(self))
+            }
+
+            #[inline]
+            fn try_upgradable(&self) -> bool {
+                $crate::accounts::permits::Permits::try_upgradable($crate::delegate_impl::DelegateAccountImpl::permits(self))
+            }
+
+            #[inline]
+            fn lock_upgradable(&self) -> bool {
+                $crate::accounts::permits::Permits::lock_upgradable($crate::delegate_impl::DelegateAccountImpl::permits(self))
+            }
+
+            #[inline]
+            unsafe fn try_upgrade(&self) -> bool {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    $crate::accounts::permits::Permits::try_upgrade($crate::delegate_impl::DelegateAccountImpl::permits(self))
+                }
+            }
+
+            #[inline]
+            unsafe fn upgrade(&self) -> bool {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    $crate::accounts::permits::Permits::upgrade($crate::delegate_impl::DelegateAccountImpl::permits(self))
+                }
+            }
+
+            #[inline]
+            unsafe fn downgrade_upgradable(&self) {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    $crate::accounts::permits::Permits::downgrade_upgradable($crate::delegate_impl::DelegateAccountImpl::permits(self));
+                }
+            }
+
+            #[inline]
+            unsafe fn relax_to_upgradable(&self) {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    $crate::accounts::permits::Permits::relax_to_upgradable($crate::delegate_impl::DelegateAccountImpl::permits(self));
+                }
+            }
+
+            #[inline]
+            unsafe fn abandon_upgradable(&self) {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    $crate::accounts::permits::Permits::abandon_upgradable($crate::delegate_impl::DelegateAccountImpl::permits(self));
+                }
             }
 
+            #[inline]
+            unsafe fn duplicate_reference(&self) -> bool {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    $crate::accounts::permits::Permits::duplicate_reference($crate::delegate_impl::DelegateAccountImpl::permits(self))
+                }
+            }
+
+            #[inline]
             unsafe fn try_escalate(&self) -> bool {
                 unsafe {
                     // SAFETY:
                     // 1. Guaranteed by caller
-                    self.permits().try_escalate()
+                    $crate::accounts::permits::Permits::try_escalate($crate::delegate_impl::DelegateAccountImpl::permits(self))
                 }
             }
 
+            #[inline]
             unsafe fn relax_permit(&self) {
                 unsafe {
                     // SAFETY:
                     // 1. Guaranteed by caller
-                    self.permits().relax_permit()
+                    $crate::accounts::permits::Permits::relax_permit($crate::delegate_impl::DelegateAccountImpl::permits(self))
                 }
             }
 
+            #[inline]
             unsafe fn abandon_reference(&self) {
                 unsafe {
                     // SAFETY:
                     // 1. Guaranteed by caller
-                    self.permits().abandon_reference()
+                    $crate::accounts::permits::Permits::abandon_reference($crate::delegate_impl::DelegateAccountImpl::permits(self))
                 }
             }
 
+            #[inline]
+            unsafe fn abandon_reference_or_escalate(&self) -> bool {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    $crate::accounts::permits::Permits::abandon_reference_or_escalate($crate::delegate_impl::DelegateAccountImpl::permits(self))
+                }
+            }
+
+            #[inline]
+            unsafe fn abandon_upgradable_or_upgrade(&self) -> bool {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    $crate::accounts::permits::Permits::abandon_upgradable_or_upgrade($crate::delegate_impl::DelegateAccountImpl::permits(self))
+                }
+            }
+
+            #[inline]
             unsafe fn abandon_mutation(&self) {
                 unsafe {
                     // SAFETY:
                     // 1. Guaranteed by caller
-                    self.permits().abandon_mutation()
+                    $crate::accounts::permits::Permits::abandon_mutation($crate::delegate_impl::DelegateAccountImpl::permits(self))
                 }
             }
 
-            type UnderlyingLockableEntity = P::UnderlyingLockableEntity;
+            type UnderlyingLockableEntity = <<$delegator as $crate::delegate_impl::DelegateAccountImpl>::DelegatedPermits as $crate::accounts::permits::Permits>::UnderlyingLockableEntity;
 
+            #[inline]
             unsafe fn underlying(&self) -> &Self::UnderlyingLockableEntity {
                 unsafe {
                     // SAFETY:
                     // Delegated responsibility
-                    self.permits().underlying()
+                    $crate::accounts::permits::Permits::underlying($crate::delegate_impl::DelegateAccountImpl::permits(self))
                 }
             }
         }
     };
+    ($delegator:ty) => {
+        $crate::delegate_account_impl!([] $delegator);
+    };
 }
@@ -1,43 +1,250 @@
-use core::alloc;
-use std::{
-    alloc::{Allocator, Layout},
-    marker::PhantomData,
-    ops::{Deref, DerefMut},
-    ptr::NonNull,
-};
-
-use crate::accounts::{AccPtr, Account};
-
-trait LedgerAllocator<const CHUNK_SIZE: usize> {
-    type Account<'a>: Account
-    where
-        Self: 'a;
-
-    type AccountRef<'a>: Deref<Target = Self::Account<'a>>
-    where
-        Self: 'a;
-
-    type ChunkRef<'a>: DerefMut + Deref<Target = FreeListChunk<Self::Account<'a>, CHUNK_SIZE>>
-    where
-        Self: 'a;
-
-    fn allocate_chunk(&self) -> Self::ChunkRef<'_>;
-    fn allocate_account(&self) -> Self::AccountRef<'_>;
+use std::{mem::MaybeUninit, ptr::NonNull};
+
+use crate::accounts::{AccPtr, Account, balances::Balance};
+
+/// The source of fresh accounts and free-list storage for a [`Ledger`].
+///
+/// # Safety
+/// 1. Every account returned by [`LedgerAllocator::allocate_account`] must be fresh: not tracking
+///    any allocation, not exhausted, with no permits held, and not returned before.
+/// 2. [`LedgerAllocator::allocate_chunk`] must return memory valid for reads and writes of a
+///    [`FreeListChunk`], which stays valid until it is passed to
+///    [`LedgerAllocator::deallocate_chunk`].
+pub unsafe trait LedgerAllocator<const CHUNK_SIZE: usize> {
+    type Account: Account;
+
+    /// Allocate a fresh account. Called whenever the free list is empty.
+    fn allocate_account(&mut self) -> AccPtr<Self::Account>;
+
+    /// Allocate storage for a chunk of the free list. The contents are initialized by the ledger.
+    fn allocate_chunk(&mut self) -> NonNull<FreeListChunk<Self::Account, CHUNK_SIZE>> {
+        NonNull::from(Box::leak(
+            Box::<FreeListChunk<Self::Account, CHUNK_SIZE>>::new_uninit(),
+        ))
+        .cast()
+    }
+
+    /// Release storage for a chunk of the free list.
+    ///
+    /// # Safety
+    /// 1. `chunk` must have been returned by [`LedgerAllocator::allocate_chunk`] on this allocator
+    /// 2. `chunk` must not be used afterwards
+    unsafe fn deallocate_chunk(
+        &mut self,
+        chunk: NonNull<FreeListChunk<Self::Account, CHUNK_SIZE>>,
+    ) {
+        std::mem::drop(unsafe {
+            // SAFETY:
+            // 1. Allocated as a box by the default `allocate_chunk`, guaranteed by caller
+            Box::from_raw(
+                chunk
+                    .cast::<MaybeUninit<FreeListChunk<Self::Account, CHUNK_SIZE>>>()
+                    .as_ptr(),
+            )
+        })
+    }
 }
 
-struct Ledger<'a, L: LedgerAllocator<CHUNK_SIZE> + 'a, const CHUNK_SIZE: usize> {
-    current: FreeListPtr<L::Account<'a>, CHUNK_SIZE>,
+/// A free list of accounts, recycling them for new allocations.
+///
+/// The free list is stored in a doubly linked list of chunks of `CHUNK_SIZE` accounts each.
+/// All chunks before the current one are full, and all after it are empty, so pushing and
+/// popping only ever moves to a neighbouring chunk. Chunks are kept once allocated, and
+/// released when the ledger is dropped.
+pub struct Ledger<L: LedgerAllocator<CHUNK_SIZE>, const CHUNK_SIZE: usize> {
+    current: Option<FreeListPtr<L::Account, CHUNK_SIZE>>,
+    len: usize,
     allocator: L,
 }
 
-impl<'a, A: Account, L: LedgerAllocator<CHUNK_SIZE>, const CHUNK_SIZE: usize>
-    Ledger<'a, L, CHUNK_SIZE>
+// SAFETY:
+// 1. The ledger owns its chunks, and only shares the accounts on them
+unsafe impl<L: LedgerAllocator<CHUNK_SIZE> + Send, const CHUNK_SIZE: usize> Send
+    for Ledger<L, CHUNK_SIZE>
+where
+    AccPtr<L::Account>: Send,
 {
-    fn new(allocator: L) -> Self {
-        let current = FreeListPtr {
-            ptr: NonNull::from_ref(&*allocator.allocate_chunk()),
+}
+
+impl<L: LedgerAllocator<CHUNK_SIZE>, const CHUNK_SIZE: usize> Ledger<L, CHUNK_SIZE> {
+    /// Create an empty ledger. No chunks are allocated until the first account is freed.
+    pub const fn new(allocator: L) -> Self {
+        Self {
+            current: None,
+            len: 0,
+            allocator,
+        }
+    }
+
+    /// Pop an account from the free list, or allocate a fresh one if it is empty.
+    #[inline]
+    pub fn allocate(&mut self) -> AccPtr<L::Account> {
+        self.pop()
+            .unwrap_or_else(|| self.allocator.allocate_account())
+    }
+
+    /// Return an account to the free list, unless its balance is exhausted, in which case
+    /// it is retired.
+    ///
+    /// # Safety
+    /// 1. `account` must have been returned by [`Ledger::allocate`] on this ledger
+    /// 2. `account` must not be tracking an allocation and no permits may be held on it
+    /// 3. `account` must not be deallocated twice
+    #[inline]
+    pub unsafe fn deallocate(&mut self, account: AccPtr<L::Account>) {
+        if !account.exhausted() {
+            self.push(account);
+        }
+    }
+
+    /// Number of accounts on the free list.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn allocator(&self) -> &L {
+        &self.allocator
+    }
+
+    pub fn allocator_mut(&mut self) -> &mut L {
+        &mut self.allocator
+    }
+
+    /// Forget all accounts on the free list, without releasing any chunks.
+    pub fn clear(&mut self) {
+        let Some(mut chunk) = self.current else {
+            return;
+        };
+
+        loop {
+            let header = unsafe {
+                // SAFETY:
+                // 1. Chunks are live until the ledger is dropped
+                &mut chunk.as_mut().header
+            };
+            header.len = 0;
+            match header.prev {
+                Some(prev) => chunk = prev,
+                None => break,
+            }
+        }
+
+        self.current = Some(chunk);
+        self.len = 0;
+    }
+
+    fn pop(&mut self) -> Option<AccPtr<L::Account>> {
+        let mut chunk = self.current?;
+
+        let mut chunk_ref = unsafe {
+            // SAFETY:
+            // 1. Chunks are live until the ledger is dropped
+            chunk.as_mut()
         };
-        Self { current, allocator }
+        if chunk_ref.header.len == 0 {
+            chunk = chunk_ref.header.prev?;
+            self.current = Some(chunk);
+            chunk_ref = unsafe {
+                // SAFETY:
+                // 1. Chunks are live until the ledger is dropped
+                chunk.as_mut()
+            };
+        }
+
+        chunk_ref.header.len -= 1;
+        self.len -= 1;
+        chunk_ref.data[chunk_ref.header.len].take()
+    }
+
+    fn push(&mut self, account: AccPtr<L::Account>) {
+        let mut chunk = match self.current {
+            Some(chunk) => chunk,
+            None => self.new_chunk(None),
+        };
+
+        let mut chunk_ref = unsafe {
+            // SAFETY:
+            // 1. Chunks are live until the ledger is dropped
+            chunk.as_mut()
+        };
+        if chunk_ref.header.len == CHUNK_SIZE {
+            chunk = match chunk_ref.header.next {
+                Some(next) => next,
+                None => {
+                    let next = self.new_chunk(Some(chunk));
+                    chunk_ref.header.next = Some(next);
+                    next
+                }
+            };
+            chunk_ref = unsafe {
+                // SAFETY:
+                // 1. Chunks are live until the ledger is dropped
+                chunk.as_mut()
+            };
+        }
+        self.current = Some(chunk);
+
+        chunk_ref.data[chunk_ref.header.len] = Some(account);
+        chunk_ref.header.len += 1;
+        self.len += 1;
+    }
+
+    fn new_chunk(
+        &mut self,
+        prev: Option<FreeListPtr<L::Account, CHUNK_SIZE>>,
+    ) -> FreeListPtr<L::Account, CHUNK_SIZE> {
+        let chunk = self.allocator.allocate_chunk();
+        unsafe {
+            // SAFETY:
+            // 1. Valid for writes, guaranteed by `LedgerAllocator`
+            chunk.write(FreeListChunk {
+                header: FreeListHeader {
+                    len: 0,
+                    prev,
+                    next: None,
+                },
+                data: [None; CHUNK_SIZE],
+            });
+        }
+        chunk
+    }
+}
+
+impl<L: LedgerAllocator<CHUNK_SIZE>, const CHUNK_SIZE: usize> Drop for Ledger<L, CHUNK_SIZE> {
+    fn drop(&mut self) {
+        let Some(mut chunk) = self.current else {
+            return;
+        };
+
+        // Rewind to the first chunk, then release them all going forwards.
+        while let Some(prev) = unsafe {
+            // SAFETY:
+            // 1. Chunks are live until released below
+            chunk.as_ref().header.prev
+        } {
+            chunk = prev;
+        }
+
+        let mut next = Some(chunk);
+        while let Some(chunk) = next {
+            next = unsafe {
+                // SAFETY:
+                // 1. Chunks are live until released just below
+                chunk.as_ref().header.next
+            };
+            unsafe {
+                // SAFETY:
+                // 1. Allocated in `new_chunk`
+                // 2. Not reachable from the ledger any more
+                self.allocator.deallocate_chunk(chunk);
+            }
+        }
     }
 }
 
@@ -48,14 +255,11 @@ struct FreeListHeader<A: Account, const CHUNK_SIZE: usize> {
     next: Option<FreeListPtr<A, CHUNK_SIZE>>,
 }
 
-#[derive(Clone, Copy)]
-#[repr(transparent)]
-struct FreeListPtr<A: Account, const CHUNK_SIZE: usize> {
-    ptr: NonNull<FreeListChunk<A, CHUNK_SIZE>>,
-}
+type FreeListPtr<A, const CHUNK_SIZE: usize> = NonNull<FreeListChunk<A, CHUNK_SIZE>>;
 
+/// A chunk of a [`Ledger`]'s free list. Its contents are private to the ledger.
 #[repr(C)]
-struct FreeListChunk<A: Account, const CHUNK_SIZE: usize> {
+pub struct FreeListChunk<A: Account, const CHUNK_SIZE: usize> {
     header: FreeListHeader<A, CHUNK_SIZE>,
     data: [Option<AccPtr<A>>; CHUNK_SIZE],
 }
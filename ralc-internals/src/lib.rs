@@ -1,17 +1,19 @@
-#![feature(ptr_metadata)]
-#![feature(ptr_as_uninit)]
-#![feature(cast_maybe_uninit)]
-#![feature(maybe_uninit_fill)]
-#![feature(allocator_api)]
+#![cfg_attr(feature = "nightly", feature(coerce_unsized, unsize, allocator_api))]
 
-use std::ptr::NonNull;
+use std::{
+    ptr::NonNull,
+    sync::atomic::{Ordering, fence},
+    time::Instant,
+};
 
 use crate::{
-    accounts::{AccPtr, Account},
+    accounts::{AccPtr, Account, permits::WaitError, slots::DataLocation},
+    alloc::{Allocator, Global, Payload},
     marker::{Marker, U56},
 };
 
 pub mod accounts;
+pub mod alloc;
 pub mod delegate_impl;
 pub mod ledger;
 pub mod marker;
@@ -21,10 +23,13 @@ pub use private::RalcRaw;
 mod private {
     use super::*;
 
+    /// What a ralc tracked by an `A` keeps in place of a pointer to `P`.
+    type DataPtr<A, P> = <<A as Account>::Data as DataLocation<A>>::Ptr<P>;
+
     /// The raw Reallocation Counting pointer, for use in implementing libraries. Such a
     /// library should implement a transparent wrapper struct with a suitable [`Drop`] implementation.
     ///
-    /// `RalcRaw` implements four different states. These should be distinguished
+    /// `RalcRaw` implements five different states. These should be distinguished
     /// in library code by using different marker types inside the wrapper types, to allow
     /// niche optimization.
     ///
@@ -39,34 +44,164 @@ mod private {
     ///    In this case, the wrapper type's `Drop` must call [`RalcRaw::drop_ref`].
     /// 4. A `RalcRaw` can be in an "weak" state, in which it holds no permits and no responsibility.
     ///    In this case, the wrapper type's `Drop` must not call any of the dropping helper methods.
-    pub struct RalcRaw<A: Account, V: Marker, T> {
+    /// 5. A `RalcRaw` can be in an "upgradable" state, in which it holds the upgradable permit of its
+    ///    account and therefore can act as a shared reference which may later become exclusive.
+    ///    In this case, the wrapper type's `Drop` must call [`RalcRaw::drop_upgradable`].
+    ///
+    /// Every allocation advances the [`Balance`](crate::accounts::balances::Balance) of its account
+    /// twice: once when it is disowned, and once when it is freed. Relative to the count
+    /// stored in a `RalcRaw`, the account is therefore in one of three phases:
+    ///
+    /// - equal: the allocation is owned and live,
+    /// - one above: the allocation has been disowned but is kept alive by permits,
+    /// - two or more above: the allocation has been freed and the account may have been
+    ///   reassigned to a different allocation.
+    ///
+    /// The pointer to the data is kept where [`Account::Data`] says, which for accounts
+    /// keeping it themselves makes a `RalcRaw` two words.
+    pub struct RalcRaw<A: Account, V: Marker, T: ?Sized, Alloc: Allocator = Global> {
         _variant: V,
         count: U56,
         account: AccPtr<A>,
         /// # Safety invariants:
-        /// 1. `data` is always derived from a box
-        data: NonNull<T>,
+        /// 1. `data` refers to a live payload until freed
+        data: DataPtr<A, Payload<Alloc, T>>,
     }
 
-    impl<A: Account, V: Marker, T> RalcRaw<A, V, T> {
+    impl<A: Account, V: Marker, T: ?Sized> RalcRaw<A, V, T> {
         /// Create a new `RalcRaw` in the "owned" state
         ///
         /// # Safety
         /// 1. The account pointer must not be shared with another `RalcRaw` in the "owned" state
+        /// 2. The account must not be exhausted and no permits may be held on it
         #[inline]
         pub unsafe fn from_parts(ptr: AccPtr<A>, data: Box<T>) -> Self {
+            unsafe {
+                // SAFETY:
+                // 1. Guaranteed by caller
+                // 2. Guaranteed by caller
+                Self::from_payload(ptr, Payload::from_box(data))
+            }
+        }
+
+        /// Move the data out of a disowned allocation, freeing the account.
+        ///
+        /// # Safety
+        ///
+        /// 1. Self must be in a "writing" state, and counts as drop
+        /// 2. The allocation must be disowned
+        #[inline]
+        pub unsafe fn free_into_box(self) -> Box<T> {
+            let res = unsafe {
+                // SAFETY:
+                // 1. Guaranteed by invariant
+                Payload::into_box(self.payload())
+            };
+
+            unsafe {
+                // SAFETY:
+                // 1. Data moved out above
+                // 2. Guaranteed by caller
+                self.free_account();
+            }
+
+            res
+        }
+    }
+
+    impl<A: Account, V: Marker, T, Alloc: Allocator> RalcRaw<A, V, T, Alloc> {
+        /// Create a new `RalcRaw` in the "owned" state, with the data allocated by `alloc`.
+        ///
+        /// # Safety
+        /// 1. The account pointer must not be shared with another `RalcRaw` in the "owned" state
+        /// 2. The account must not be exhausted and no permits may be held on it
+        #[inline]
+        pub unsafe fn from_parts_in(ptr: AccPtr<A>, data: T, alloc: Alloc) -> Self {
+            unsafe {
+                // SAFETY:
+                // 1. Guaranteed by caller
+                // 2. Guaranteed by caller
+                Self::from_payload(ptr, Payload::new_in(data, alloc))
+            }
+        }
+
+        /// Move the data out of a disowned allocation, freeing the account.
+        ///
+        /// # Safety
+        ///
+        /// 1. Self must be in a "writing" state, and counts as drop
+        /// 2. The allocation must be disowned
+        #[inline]
+        pub unsafe fn free_into_inner(self) -> T {
+            let res = unsafe {
+                // SAFETY:
+                // 1. Guaranteed by invariant
+                Payload::into_inner(self.payload())
+            };
+
+            unsafe {
+                // SAFETY:
+                // 1. Data moved out above
+                // 2. Guaranteed by caller
+                self.free_account();
+            }
+
+            res
+        }
+    }
+
+    impl<A: Account, V: Marker, T: ?Sized, Alloc: Allocator> RalcRaw<A, V, T, Alloc> {
+        /// Create a new `RalcRaw` in the "owned" state, from a payload which is already
+        /// in place.
+        ///
+        /// # Safety
+        /// 1. The account pointer must not be shared with another `RalcRaw` in the "owned" state
+        /// 2. The account must not be exhausted and no permits may be held on it
+        /// 3. `data` must be a live payload, which is only used through this `RalcRaw` until
+        ///    freed by it
+        #[inline]
+        pub unsafe fn from_payload(ptr: AccPtr<A>, data: NonNull<Payload<Alloc, T>>) -> Self {
             RalcRaw {
                 _variant: V::default(),
                 count: ptr.check().into(),
                 account: ptr,
-                data: unsafe { NonNull::new_unchecked(Box::into_raw(data)) },
+                data: unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    A::Data::track(&ptr, data)
+                },
+            }
+        }
+
+        /// Create a new `RalcRaw` in the "weak" state, for the allocation which the account
+        /// tracked at reallocation count `count`.
+        ///
+        /// # Safety
+        /// 1. The account must have tracked `data` in the "owned" state at count `count`, as
+        ///    [`RalcRaw::generation`] of a pointer to it returns
+        #[inline]
+        pub unsafe fn from_generation(
+            ptr: AccPtr<A>,
+            count: u64,
+            data: NonNull<Payload<Alloc, T>>,
+        ) -> Self {
+            RalcRaw {
+                _variant: V::default(),
+                count: count.into(),
+                account: ptr,
+                data: A::Data::recall(data),
             }
         }
 
-        /// TODO
+        /// The reallocation count at which the account tracked this allocation while owned.
+        #[inline]
+        pub fn generation(self) -> u64 {
+            self.count.into()
+        }
+
         /// Change the marker type
         #[inline]
-        pub fn switch_makrer<W: Marker>(self) -> RalcRaw<A, W, T> {
+        pub fn switch_marker<W: Marker>(self) -> RalcRaw<A, W, T, Alloc> {
             RalcRaw {
                 _variant: W::default(),
                 count: self.count,
@@ -75,7 +210,46 @@ mod private {
             }
         }
 
-        /// TODO
+        /// The account tracking this allocation.
+        #[inline]
+        pub fn account(&self) -> &A {
+            &self.account
+        }
+
+        /// The pointer to the allocated data.
+        ///
+        /// This may only be dereferenced while a permit is held, and only
+        /// in accordance with the kind of permit.
+        ///
+        /// # Safety
+        /// 1. The allocation must not have been freed, as ensured by holding a permit
+        #[inline]
+        pub unsafe fn data(self) -> NonNull<T> {
+            unsafe {
+                // SAFETY:
+                // 1. Guaranteed by caller
+                Payload::data(self.payload())
+            }
+        }
+
+        /// The pointer to the payload holding the allocated data.
+        ///
+        /// Once the allocation has been freed, this may dangle or point to whichever payload
+        /// the account has since been assigned.
+        ///
+        /// # Safety
+        /// 1. Unless `T` is sized, the allocation must not have been freed
+        #[inline]
+        pub unsafe fn payload(self) -> NonNull<Payload<Alloc, T>> {
+            unsafe {
+                // SAFETY:
+                // 1. Guaranteed by caller
+                A::Data::data(&self.account, self.data)
+            }
+        }
+
+        /// Increment the reallocation count, relinquishing ownership.
+        ///
         /// # Safety
         /// 1. This pointer must be in the "owned" state
         #[inline]
@@ -83,34 +257,128 @@ mod private {
             self.account.invalidate();
         }
 
-        /// TODO
+        /// Check whether the allocation is still owned, i.e. whether the reallocation
+        /// count of the account still matches.
         #[inline]
-        pub fn is_disowned(self) -> bool {
+        pub fn is_owned(self) -> bool {
             self.account.check() == self.count.into()
         }
 
-        /// TODO
+        /// Check whether the allocation has been disowned, or even freed.
+        #[inline]
+        pub fn is_disowned(self) -> bool {
+            !self.is_owned()
+        }
+
+        /// Check whether the allocation has been freed, in which case the account may
+        /// have been reassigned.
+        #[inline]
+        pub fn is_freed(self) -> bool {
+            self.account.check() > u64::from(self.count) + 1
+        }
+
+        /// Drop the data and free the account.
+        ///
         /// # Safety
         /// 1. Counts as drop
         /// 2. A mutation permit must be held
+        /// 3. The allocation must be disowned but not freed
         #[inline]
         unsafe fn drop_with_mutation(self) {
-            let alloc = unsafe {
+            unsafe {
                 // SAFETY:
                 // 1. Guaranteed by invariant
-                Box::from_raw(self.data.as_ptr())
-            };
-            std::mem::drop(alloc);
+                Payload::free(self.payload());
+            }
+
+            unsafe {
+                // SAFETY:
+                // 1. Guaranteed by caller
+                // 2. Guaranteed by caller
+                self.free_account();
+            }
+        }
+
+        /// Mark the allocation as freed and return the account.
+        ///
+        /// # Safety
+        /// 1. Counts as drop, and the data must already have been dropped or moved out
+        /// 2. A mutation permit must be held
+        #[inline]
+        unsafe fn free_account(self) {
+            self.account.invalidate();
 
             unsafe {
                 // SAFETY:
                 // 1. Guaranteed by caller
-                // 2. Ensured by call to try_mutation above
+                // 2. Guaranteed by caller
                 self.account.free();
             }
         }
 
-        /// TODO
+        /// Drop the allocation if it has been disowned, holding the mutation permit which
+        /// was the last permit on it.
+        ///
+        /// Pairs with [`RalcRaw::drop_box`]: whoever of the two comes last sees the other,
+        /// and both may end up holding the mutation permit one after the other, so only
+        /// the first drops the allocation.
+        ///
+        /// # Safety
+        /// 1. Counts as drop
+        /// 2. The mutation permit must be held
+        #[inline]
+        unsafe fn drop_with_last_permit(self) {
+            if self.is_owned() {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    self.account.abandon_mutation();
+                }
+
+                // Either the owner sees the permit relinquished, or this sees it disowned.
+                fence(Ordering::SeqCst);
+                if !self.is_disowned() || self.is_freed() || !self.account.try_mutation_unfair() {
+                    return;
+                }
+            }
+
+            unsafe {
+                // SAFETY:
+                // 1. Guaranteed by caller
+                // 2. Guaranteed by caller, or try_mutation_unfair called above
+                // 3. Checked above
+                self.drop_disowned_with_mutation();
+            }
+        }
+
+        /// Drop the allocation unless someone else has already, holding the mutation
+        /// permit.
+        ///
+        /// # Safety
+        /// 1. Counts as drop
+        /// 2. The mutation permit must be held
+        /// 3. The allocation must be disowned
+        #[inline]
+        unsafe fn drop_disowned_with_mutation(self) {
+            if self.is_freed() {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    self.account.abandon_mutation();
+                }
+            } else {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    // 2. Guaranteed by caller
+                    // 3. Guaranteed by caller, and checked above
+                    self.drop_with_mutation();
+                }
+            }
+        }
+
+        /// Disown the allocation, and drop it if no permits are held.
+        ///
         /// # Safety
         /// 1. This must only be called during drop, and counts as having dropped the underlying data
         ///    and tracking account.
@@ -123,59 +391,169 @@ mod private {
                 self.disown();
             }
 
-            if self.account.try_mutation() {
+            // Pairs with the fence in `drop_with_last_permit`.
+            fence(Ordering::SeqCst);
+            if self.account.try_mutation_unfair() {
                 unsafe {
                     // SAFETY:
                     // 1. Guaranteed by caller
-                    // 2. try_mutation called above
-                    self.drop_with_mutation();
+                    // 2. try_mutation_unfair called above
+                    // 3. Disowned just above
+                    self.drop_disowned_with_mutation();
                 }
             }
         }
 
-        /// TODO
+        /// Relinquish a reference permit, dropping the allocation if it has been
+        /// disowned and this was the last permit.
+        ///
         /// # Safety
         /// 1. This must only be called during drop, and counts as having dropped the underlying data
         ///    and tracking account.
         /// 2. This must only be called if `self` is in the "reading" state.
         #[inline]
         pub unsafe fn drop_ref(self) {
-            if self.is_disowned() {
-                return;
-            }
-
+            // Relinquishing and escalating must be one step, or the last two readers may
+            // both see the other and neither drop the allocation.
             let escalated = unsafe {
                 // SAFETY:
                 // 1. Guaranteed by "reading" state
-                self.account.try_escalate()
+                self.account.abandon_reference_or_escalate()
             };
 
             if escalated {
                 unsafe {
                     // SAFETY:
                     // 1. Guaranteed by caller
-                    // 2. try_escalate called above
-                    self.drop_with_mutation();
+                    // 2. Escalated above
+                    self.drop_with_last_permit();
                 }
             }
         }
 
-        /// TODO
+        /// Relinquish the mutation permit, dropping the allocation if it has been disowned.
+        ///
         /// # Safety
         /// 1. This must only be called during drop, and counts as having dropped the underlying data
         ///    and tracking account.
         /// 2. This must only be called if `self` is in the "writing" state.
         #[inline]
         pub unsafe fn drop_mut(self) {
-            if self.is_disowned() {
-                return;
-            }
-
             unsafe {
                 // SAFETY:
                 // 1. Guaranteed by caller
                 // 2. Guaranteed by "writing" state
-                self.drop_with_mutation();
+                self.drop_with_last_permit();
+            }
+        }
+
+        /// Relinquish a reference permit acquired through a "weak" pointer which turned
+        /// out to be disowned by the time the permit was acquired.
+        ///
+        /// If the allocation is merely disowned this behaves as [`RalcRaw::drop_ref`]. If
+        /// it has been freed, the permit belongs to whichever allocation the account has
+        /// since been assigned to, and is only relinquished.
+        ///
+        /// # Safety
+        /// 1. Counts as drop
+        /// 2. A reference permit must have been acquired on the account
+        #[inline]
+        pub unsafe fn drop_stale_ref(self) {
+            if self.is_freed() {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    self.account.abandon_reference();
+                }
+            } else {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    // 2. Guaranteed by caller
+                    self.drop_ref();
+                }
+            }
+        }
+
+        /// Relinquish a mutation permit acquired through a "weak" pointer which turned
+        /// out to be disowned by the time the permit was acquired.
+        ///
+        /// If the allocation is merely disowned this behaves as [`RalcRaw::drop_mut`]. If
+        /// it has been freed, the permit belongs to whichever allocation the account has
+        /// since been assigned to, and is only relinquished.
+        ///
+        /// # Safety
+        /// 1. Counts as drop
+        /// 2. The mutation permit must have been acquired on the account
+        #[inline]
+        pub unsafe fn drop_stale_mut(self) {
+            if self.is_freed() {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    self.account.abandon_mutation();
+                }
+            } else {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    // 2. Guaranteed by caller
+                    self.drop_mut();
+                }
+            }
+        }
+
+        /// Relinquish the upgradable permit, dropping the allocation if it has been
+        /// disowned and no reference permits remain.
+        ///
+        /// # Safety
+        /// 1. This must only be called during drop, and counts as having dropped the underlying data
+        ///    and tracking account.
+        /// 2. This must only be called if `self` is in the "upgradable" state.
+        #[inline]
+        pub unsafe fn drop_upgradable(self) {
+            // As in `drop_ref`, relinquishing and upgrading must be one step.
+            let upgraded = unsafe {
+                // SAFETY:
+                // 1. Guaranteed by "upgradable" state
+                self.account.abandon_upgradable_or_upgrade()
+            };
+
+            if upgraded {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    // 2. Upgraded above
+                    self.drop_with_last_permit();
+                }
+            }
+        }
+
+        /// Relinquish an upgradable permit acquired through a "weak" pointer which turned
+        /// out to be disowned by the time the permit was acquired.
+        ///
+        /// If the allocation is merely disowned this behaves as [`RalcRaw::drop_upgradable`].
+        /// If it has been freed, the permit belongs to whichever allocation the account has
+        /// since been assigned to, and is only relinquished.
+        ///
+        /// # Safety
+        /// 1. Counts as drop
+        /// 2. The upgradable permit must have been acquired on the account
+        #[inline]
+        pub unsafe fn drop_stale_upgradable(self) {
+            if self.is_freed() {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    self.account.abandon_upgradable();
+                }
+            } else {
+                unsafe {
+                    // SAFETY:
+                    // 1. Guaranteed by caller
+                    // 2. Guaranteed by caller
+                    self.drop_upgradable();
+                }
             }
         }
 
@@ -191,6 +569,8 @@ mod private {
         #[inline]
         pub unsafe fn try_reclaim_dropped_box(self) -> Option<Self> {
             if self.is_disowned() {
+                // Skip past the "freed" phase of the previous owner's pointers.
+                self.account.invalidate();
                 let owned = Self {
                     count: self.account.check().into(),
                     ..self
@@ -217,6 +597,8 @@ mod private {
         #[inline]
         pub unsafe fn try_reclaim_dropped_box_retaining_mut(&mut self) -> Option<Self> {
             if self.is_disowned() {
+                // Skip past the "freed" phase of the previous owner's pointers.
+                self.account.invalidate();
                 self.count = self.account.check().into();
 
                 Some(*self)
@@ -244,10 +626,79 @@ mod private {
             if escalated { Some(self) } else { None }
         }
 
-        /// TODO
+        /// Attempt to turn an "upgradable" state reference into a "writing" state one,
+        /// which only succeeds if no reference permits are held.
+        ///
+        /// If `None` is returned, the original reference is still in a valid "upgradable" state.
+        /// If `Some` is returned, the original reference is now "weak".
+        ///
         /// # Safety
         ///
-        /// 1. Self must be in a "writing" state
+        /// 1. Self must be in an "upgradable" state
+        #[inline]
+        pub unsafe fn try_upgrade_upgradable_into_mut(self) -> Option<Self> {
+            let upgraded = unsafe {
+                // SAFETY:
+                // 1. Guaranteed by caller
+                self.account.try_upgrade()
+            };
+            if upgraded { Some(self) } else { None }
+        }
+
+        /// Wait for all reference permits to be relinquished, then turn an "upgradable" state
+        /// reference into a "writing" state one. Returns `None` if waiting is not supported.
+        ///
+        /// If `None` is returned, the original reference is still in a valid "upgradable" state.
+        /// If `Some` is returned, the original reference is now "weak".
+        ///
+        /// # Safety
+        ///
+        /// 1. Self must be in an "upgradable" state
+        #[inline]
+        pub unsafe fn upgrade_upgradable_into_mut(self) -> Option<Self> {
+            let upgraded = unsafe {
+                // SAFETY:
+                // 1. Guaranteed by caller
+                self.account.upgrade()
+            };
+            if upgraded { Some(self) } else { None }
+        }
+
+        /// Turn an "upgradable" state reference into a "reading" state one.
+        ///
+        /// # Safety
+        ///
+        /// 1. Self must be in an "upgradable" state, and is "weak" afterwards
+        #[inline]
+        pub unsafe fn downgrade_upgradable_into_ref(self) -> Self {
+            unsafe {
+                // SAFETY:
+                // 1. Guaranteed by caller
+                self.account.downgrade_upgradable();
+            }
+            self
+        }
+
+        /// Turn a "writing" state reference into an "upgradable" state one.
+        ///
+        /// # Safety
+        ///
+        /// 1. Self must be in a "writing" state, and is "weak" afterwards
+        #[inline]
+        pub unsafe fn downgrade_mut_into_upgradable(self) -> Self {
+            unsafe {
+                // SAFETY:
+                // 1. Guaranteed by caller
+                self.account.relax_to_upgradable();
+            }
+            self
+        }
+
+        /// Turn a "writing" state reference into a "reading" state one.
+        ///
+        /// # Safety
+        ///
+        /// 1. Self must be in a "writing" state, and is "weak" afterwards
         #[inline]
         pub unsafe fn downgrade_mut_into_ref(self) -> Self {
             unsafe {
@@ -258,22 +709,31 @@ mod private {
             self
         }
 
-        /// TODO
+        /// Acquire another reference permit, returning a new "reading" state reference.
+        ///
+        /// # Panics
+        ///
+        /// If the account cannot hand out any more reference permits.
+        ///
         /// # Safety
         ///
         /// 1. Self must be in a "reading" state
         #[inline]
         pub unsafe fn clone_ref(self) -> Self {
-            self.account.try_reference();
+            let duplicated = unsafe {
+                // SAFETY:
+                // 1. Guaranteed by caller
+                self.account.duplicate_reference()
+            };
+            assert!(duplicated, "too many reference permits");
             self
         }
 
-        /// TODO
-        /// # Safety
+        /// Acquire a reference permit, returning a "reading" state reference.
         ///
-        /// 1. Self must be in an "owned" state
+        /// This does not check the reallocation count.
         #[inline]
-        pub unsafe fn try_acquire_ref(self) -> Option<Self> {
+        pub fn try_acquire_ref(self) -> Option<Self> {
             if self.account.try_reference() {
                 Some(self)
             } else {
@@ -281,12 +741,11 @@ mod private {
             }
         }
 
-        /// TODO
-        /// # Safety
+        /// Acquire the mutation permit, returning a "writing" state reference.
         ///
-        /// 1. Self must be in an "owned" state
+        /// This does not check the reallocation count.
         #[inline]
-        pub unsafe fn try_acquire_mut(self) -> Option<Self> {
+        pub fn try_acquire_mut(self) -> Option<Self> {
             if self.account.try_mutation() {
                 Some(self)
             } else {
@@ -294,16 +753,95 @@ mod private {
             }
         }
 
+        /// Wait for a reference permit, returning a "reading" state reference.
+        /// Returns `None` if waiting is not supported.
+        ///
+        /// This does not check the reallocation count.
+        #[inline]
+        pub fn acquire_ref(self) -> Option<Self> {
+            if self.account.lock_reference() {
+                Some(self)
+            } else {
+                None
+            }
+        }
+
+        /// Wait for a reference permit without making way for writers, as the current
+        /// thread already holds one, returning a "reading" state reference. Returns `None`
+        /// if waiting is not supported.
+        ///
+        /// This does not check the reallocation count.
+        #[inline]
+        pub fn acquire_ref_recursive(self) -> Option<Self> {
+            if self.account.lock_reference_recursive() {
+                Some(self)
+            } else {
+                None
+            }
+        }
+
+        /// Wait for the mutation permit, returning a "writing" state reference.
+        /// Returns `None` if waiting is not supported.
+        ///
+        /// This does not check the reallocation count.
+        #[inline]
+        pub fn acquire_mut(self) -> Option<Self> {
+            if self.account.lock_mutation() {
+                Some(self)
+            } else {
+                None
+            }
+        }
+
+        /// Wait for a reference permit until `deadline`, returning a "reading" state reference.
+        ///
+        /// This does not check the reallocation count.
+        #[inline]
+        pub fn acquire_ref_until(self, deadline: Instant) -> Result<Self, WaitError> {
+            self.account.lock_reference_until(deadline).map(|()| self)
+        }
+
+        /// Wait for the mutation permit until `deadline`, returning a "writing" state reference.
+        ///
+        /// This does not check the reallocation count.
+        #[inline]
+        pub fn acquire_mut_until(self, deadline: Instant) -> Result<Self, WaitError> {
+            self.account.lock_mutation_until(deadline).map(|()| self)
+        }
+
+        /// Acquire the upgradable permit, returning an "upgradable" state reference.
+        ///
+        /// This does not check the reallocation count.
+        #[inline]
+        pub fn try_acquire_upgradable(self) -> Option<Self> {
+            if self.account.try_upgradable() {
+                Some(self)
+            } else {
+                None
+            }
+        }
+
+        /// Wait for the upgradable permit, returning an "upgradable" state reference.
+        /// Returns `None` if waiting is not supported.
+        ///
+        /// This does not check the reallocation count.
+        #[inline]
+        pub fn acquire_upgradable(self) -> Option<Self> {
+            if self.account.lock_upgradable() {
+                Some(self)
+            } else {
+                None
+            }
+        }
+
+        /// Acquire the mutation permit and disown the allocation, returning
+        /// a "writing" state reference which will drop it.
+        ///
         /// # Safety
-        /// TODO
-        /// 1. "owned"
+        /// 1. Self must be in an "owned" state, and is invalid afterwards if `Some` is returned
         #[inline]
         pub unsafe fn disown_into_mut(self) -> Option<Self> {
-            let res = unsafe {
-                // SAFETY:
-                //
-                self.try_acquire_mut()?
-            };
+            let res = self.try_acquire_mut()?;
             unsafe {
                 // SAFETY:
                 // 1. Guaranteed by caller
@@ -312,16 +850,14 @@ mod private {
             Some(res)
         }
 
+        /// Acquire a reference permit and disown the allocation, returning
+        /// a "reading" state reference which will drop it.
+        ///
         /// # Safety
-        /// TODO
-        /// 1. "owned"
+        /// 1. Self must be in an "owned" state, and is invalid afterwards if `Some` is returned
         #[inline]
         pub unsafe fn disown_into_ref(self) -> Option<Self> {
-            let res = unsafe {
-                // SAFETY:
-                // 1. Guaranteed by caller
-                self.try_acquire_mut()?
-            };
+            let res = self.try_acquire_ref()?;
             unsafe {
                 // SAFETY:
                 // 1. Guaranteed by caller
@@ -331,11 +867,23 @@ mod private {
         }
     }
 
-    impl<A: Account, V: Marker, T> Clone for RalcRaw<A, V, T> {
+    impl<A: Account, V: Marker, T: ?Sized, Alloc: Allocator> Clone for RalcRaw<A, V, T, Alloc> {
         fn clone(&self) -> Self {
             *self
         }
     }
 
-    impl<A: Account, V: Marker, T> Copy for RalcRaw<A, V, T> {}
+    impl<A: Account, V: Marker, T: ?Sized, Alloc: Allocator> Copy for RalcRaw<A, V, T, Alloc> {}
+
+    #[cfg(feature = "nightly")]
+    impl<A, V, T, U, Alloc> std::ops::CoerceUnsized<RalcRaw<A, V, U, Alloc>> for RalcRaw<A, V, T, Alloc>
+    where
+        A: Account,
+        V: Marker,
+        T: ?Sized + std::marker::Unsize<U>,
+        U: ?Sized,
+        Alloc: Allocator,
+        DataPtr<A, Payload<Alloc, T>>: std::ops::CoerceUnsized<DataPtr<A, Payload<Alloc, U>>>,
+    {
+    }
 }
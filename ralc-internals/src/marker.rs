@@ -1,4 +1,4 @@
-/// # Safety requirements
+/// # Safety
 /// 1. Implementor must be `#[repr(transparent(u8)]`.
 /// 2. Must only have one variant
 pub unsafe trait Marker: Copy + Default {}
@@ -25,33 +25,25 @@ pub struct U56([u8; 7]);
 impl From<u64> for U56 {
     fn from(value: u64) -> Self {
         #[cfg(target_endian = "little")]
-        {
-            let b = value.to_le_bytes();
-            return U56([b[0], b[1], b[2], b[3], b[4], b[5], b[6]]);
-        }
+        let [b0, b1, b2, b3, b4, b5, b6, _] = value.to_le_bytes();
 
         #[cfg(target_endian = "big")]
-        {
-            let b = n.to_be_bytes();
-            return U56([b[1], b[2], b[3], b[4], b[5], b[6], b[7]]);
-        }
+        let [_, b0, b1, b2, b3, b4, b5, b6] = value.to_be_bytes();
+
+        U56([b0, b1, b2, b3, b4, b5, b6])
     }
 }
 
 impl From<U56> for u64 {
     fn from(value: U56) -> Self {
-        let s = value.0;
+        let [b0, b1, b2, b3, b4, b5, b6] = value.0;
 
         #[cfg(target_endian = "big")]
-        {
-            let bytes = [0, s[0], s[1], s[2], s[3], s[4], s[5], s[6]];
-            return u64::from_be_bytes(bytes);
-        }
+        let res = u64::from_be_bytes([0, b0, b1, b2, b3, b4, b5, b6]);
 
         #[cfg(target_endian = "little")]
-        {
-            let bytes = [s[0], s[1], s[2], s[3], s[4], s[5], s[6], 0];
-            return u64::from_le_bytes(bytes);
-        }
+        let res = u64::from_le_bytes([b0, b1, b2, b3, b4, b5, b6, 0]);
+
+        res
     }
 }
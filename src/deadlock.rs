@@ -0,0 +1,148 @@
+use crate::NoAccess;
+
+/// Identity of the account tracking a ralc, as reported by [`NoAccess::Deadlock`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AccountId(usize);
+
+impl AccountId {
+    pub(crate) fn of<A>(account: &A) -> Self {
+        Self(std::ptr::from_ref(account).addr())
+    }
+}
+
+/// A permit held by a guard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Held {
+    Reference,
+    Upgradable,
+    Mutation,
+}
+
+/// Identifies the entry of a guard, so that it is forgotten wherever the guard is released.
+pub(crate) struct Token(#[cfg(feature = "deadlock-detection")] u64);
+
+#[cfg(feature = "deadlock-detection")]
+mod registry {
+    use std::{
+        collections::HashMap,
+        sync::{
+            LazyLock, Mutex, MutexGuard,
+            atomic::{AtomicU64, Ordering},
+        },
+        thread::{self, ThreadId},
+    };
+
+    use super::{AccountId, Held, Token};
+
+    /// The threads holding permits on each account, and the tokens of their guards.
+    type Holders = HashMap<AccountId, Vec<(ThreadId, Held, u64)>>;
+
+    static HELD: LazyLock<Mutex<Holders>> = LazyLock::new(Default::default);
+
+    static NEXT_TOKEN: AtomicU64 = AtomicU64::new(0);
+
+    fn held() -> MutexGuard<'static, Holders> {
+        HELD.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn acquired(account: AccountId, permit: Held) -> Token {
+        let token = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+        held()
+            .entry(account)
+            .or_default()
+            .push((thread::current().id(), permit, token));
+        Token(token)
+    }
+
+    pub(crate) fn released(account: AccountId, token: &Token) {
+        let mut held = held();
+        let Some(holders) = held.get_mut(&account) else {
+            return;
+        };
+
+        if let Some(index) = holders.iter().position(|&(_, _, t)| t == token.0) {
+            holders.swap_remove(index);
+        }
+        if holders.is_empty() {
+            held.remove(&account);
+        }
+    }
+
+    pub(crate) fn held_by_current(account: AccountId, conflicts: impl Fn(Held) -> bool) -> bool {
+        let current = thread::current().id();
+        held().get(&account).is_some_and(|holders| {
+            holders
+                .iter()
+                .any(|&(t, p, _)| t == current && conflicts(p))
+        })
+    }
+}
+
+/// Record a guard acquired on the current thread.
+#[inline]
+pub(crate) fn acquired(account: AccountId, permit: Held) -> Token {
+    #[cfg(feature = "deadlock-detection")]
+    return registry::acquired(account, permit);
+    #[cfg(not(feature = "deadlock-detection"))]
+    {
+        let _ = (account, permit);
+        Token()
+    }
+}
+
+/// Forget a guard, on whichever thread it was recorded.
+#[inline]
+pub(crate) fn released(account: AccountId, token: &Token) {
+    #[cfg(feature = "deadlock-detection")]
+    registry::released(account, token);
+    #[cfg(not(feature = "deadlock-detection"))]
+    let _ = (account, token);
+}
+
+/// Fail if the current thread holds a permit which keeps it from waiting for `wanted`.
+///
+/// Guards sent to another thread still count as held by the thread which acquired them,
+/// until they are released.
+#[inline]
+pub(crate) fn check<G>(account: AccountId, wanted: Held) -> Result<(), NoAccess<G>> {
+    #[cfg(feature = "deadlock-detection")]
+    {
+        let conflicts = |held| match wanted {
+            Held::Reference => held == Held::Mutation,
+            Held::Upgradable => held != Held::Reference,
+            Held::Mutation => true,
+        };
+        if registry::held_by_current(account, conflicts) {
+            return Err(NoAccess::Deadlock(Some(account)));
+        }
+    }
+    #[cfg(not(feature = "deadlock-detection"))]
+    let _ = (account, wanted);
+    Ok(())
+}
+
+/// Whether the current thread holds a reference permit, so that waiting for another one
+/// must not make way for writers, which would wait for it in turn.
+#[inline]
+pub(crate) fn holds_reference(account: AccountId) -> bool {
+    #[cfg(feature = "deadlock-detection")]
+    return registry::held_by_current(account, |held| held == Held::Reference);
+    #[cfg(not(feature = "deadlock-detection"))]
+    {
+        let _ = account;
+        false
+    }
+}
+
+/// Fail if the current thread holds a reader which keeps its upgradable permit from
+/// being upgraded.
+#[inline]
+pub(crate) fn check_upgrade(account: AccountId) -> Result<(), AccountId> {
+    #[cfg(feature = "deadlock-detection")]
+    if registry::held_by_current(account, |held| held == Held::Reference) {
+        return Err(account);
+    }
+    #[cfg(not(feature = "deadlock-detection"))]
+    let _ = account;
+    Ok(())
+}
@@ -1,40 +1,139 @@
-mod ledgers;
-
 use std::{
-    ptr::null_mut,
-    sync::atomic::{AtomicPtr, AtomicU64, Ordering},
-}; 
+    cell::RefCell,
+    sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+    time::Instant,
+};
 
-use parking_lot::{Mutex, lock_api::{
-    RawRwLock, RawRwLockDowngrade, RawRwLockUpgrade, RawRwLockUpgradeDowngrade,
-}};
+use parking_lot::lock_api::{
+    RawRwLock, RawRwLockDowngrade, RawRwLockRecursive, RawRwLockTimed, RawRwLockUpgrade,
+    RawRwLockUpgradeDowngrade,
+};
 use ralc_internals::{
-    accounts::{freeable::Freeable, permits::Permits},
+    accounts::{
+        AccPtr, Account,
+        balances::Balance,
+        freeable::Freeable,
+        permits::{Permits, WaitError},
+        slots::{AtomicErasedPtr, DataSlot, ErasedPtr, InAccount},
+        waiting::Waitable,
+    },
+    delegate_account_impl,
     delegate_impl::DelegateAccountImpl,
 };
 
-static GLOBAL_ALLOCATOR : Mutex<Allocator<GlobalAccount>> = Mutex::new()
+use crate::{
+    ImplicitAccount,
+    ledgers::{Linked, LockFreeBook},
+};
+
+static GLOBAL_ALLOCATOR: LockFreeBook<GlobalAccount> = LockFreeBook::new();
 
-struct GlobalAccount(AtomicU64, ParkingLock);
+static MAGAZINE_SIZE: AtomicUsize = AtomicUsize::new(0);
 
-impl Default for GlobalAccount {
-    fn default() -> Self {
-        Self(Default::default(), Default::default())
+thread_local! {
+    static MAGAZINE: RefCell<Magazine> = const { RefCell::new(Magazine(Vec::new())) };
+}
+
+/// Per-thread cache of free global accounts, refilled from and flushed to the global book
+/// half a magazine at a time.
+struct Magazine(Vec<&'static GlobalAccount>);
+
+impl Drop for Magazine {
+    fn drop(&mut self) {
+        unsafe {
+            // SAFETY:
+            // 1. Cached accounts were allocated by the global book
+            // 2. Cached accounts were freed, or never handed out
+            // 3. Cached accounts are only ever in one magazine or on the free list
+            GLOBAL_ALLOCATOR.deallocate_batch(self.0.drain(..));
+        }
+    }
+}
+
+/// Account type for ralcs which are globally shareable.
+///
+/// Global accounts are leaked to `'static` lifetime and recycled
+/// through a process-wide, lock-free free list. Their permits can be
+/// waited for both blocking and asynchronously.
+pub type Global = GlobalAccount;
+
+/// Handle for tuning the process-wide allocator of [`Global`] accounts.
+pub struct GlobalAllocator;
+
+impl GlobalAllocator {
+    /// Set the number of accounts allocated in the next chunk, and
+    /// the limit to which subsequent chunk sizes may grow.
+    pub fn set_chunks(chunk: usize, limit: usize) {
+        GLOBAL_ALLOCATOR.set_chunks(chunk, limit)
+    }
+
+    /// Set the number of free accounts each thread may cache before returning them to the
+    /// process-wide free list. Zero, the default, disables the cache.
+    ///
+    /// Threads refill an empty cache and flush a full one half a magazine at a time, and
+    /// return their cached accounts when they exit.
+    pub fn set_magazine_size(size: usize) {
+        MAGAZINE_SIZE.store(size, Ordering::Relaxed);
+    }
+
+    /// Return the current thread's cached accounts to the process-wide free list.
+    pub fn flush_magazine() {
+        let _ = MAGAZINE.try_with(|magazine| {
+            unsafe {
+                // SAFETY:
+                // See `Magazine::drop`
+                GLOBAL_ALLOCATOR.deallocate_batch(magazine.borrow_mut().0.drain(..));
+            }
+        });
+    }
+
+    #[cfg(test)]
+    pub(crate) fn cached() -> usize {
+        MAGAZINE.with(|magazine| magazine.borrow().0.len())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn total_allocations() -> usize {
+        GLOBAL_ALLOCATOR.total_allocations()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn expansions() -> usize {
+        GLOBAL_ALLOCATOR.expansions()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn free_count() -> usize {
+        GLOBAL_ALLOCATOR.free_count()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn reset() {
+        MAGAZINE.with(|magazine| magazine.borrow_mut().0.clear());
+        GLOBAL_ALLOCATOR.reset()
     }
 }
 
-fn alloc() -> &'static GlobalAccount {
-    let mut res: &'static GlobalAccount;
-    loop {
-        let free = FREE_LIST.load(Ordering::Relaxed);
-        res = free.as_ref()
+/// The balance, the permits, the free list link and index, and the data slot of the account.
+pub struct GlobalAccount(
+    AtomicU64,
+    Waitable<ParkingLock>,
+    AtomicU32,
+    u32,
+    AtomicErasedPtr,
+);
+
+impl GlobalAccount {
+    #[cfg(test)]
+    pub(crate) fn set_balance(&self, value: u64) {
+        self.0.store(value, Ordering::Relaxed);
     }
 }
 
-impl DelegateAccountImpl for &'static GlobalAccount {
+impl DelegateAccountImpl for GlobalAccount {
     type DelegatedBalance = AtomicU64;
 
-    type DelegatedPermits = ParkingLock;
+    type DelegatedPermits = Waitable<ParkingLock>;
 
     fn balance(&self) -> &Self::DelegatedBalance {
         &self.0
@@ -45,6 +144,8 @@ impl DelegateAccountImpl for &'static GlobalAccount {
     }
 }
 
+delegate_account_impl!(GlobalAccount);
+
 unsafe impl Freeable for GlobalAccount {
     unsafe fn free(&self) {
         unsafe {
@@ -52,17 +153,140 @@ unsafe impl Freeable for GlobalAccount {
             // Guaranteed by caller.
             self.abandon_mutation();
         }
+
+        unsafe {
+            // SAFETY:
+            // 1. Global accounts are only allocated by the global book, in leaked chunks
+            // 2. Guaranteed by caller
+            // 3. Guaranteed by caller
+            let account = &*(self as *const Self);
+            if !account.exhausted() && cache(account) {
+                return;
+            }
+            GLOBAL_ALLOCATOR.deallocate(account);
+        }
         // IMPL SAFETY:
         // 1. See above
     }
 }
 
+impl Account for GlobalAccount {
+    type Data = InAccount;
+}
+
+// SAFETY:
+// 1. Delegated implementation
+unsafe impl DataSlot for GlobalAccount {
+    #[inline]
+    unsafe fn set_data(&self, data: ErasedPtr) {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.4.set_data(data)
+        }
+    }
+
+    #[inline]
+    fn data(&self) -> ErasedPtr {
+        self.4.data()
+    }
+}
+
+impl Linked for GlobalAccount {
+    fn new_linked(index: u32) -> Self {
+        Self(
+            AtomicU64::new(0),
+            Waitable::new(ParkingLock::new()),
+            AtomicU32::new(0),
+            index,
+            AtomicErasedPtr::new(),
+        )
+    }
+
+    fn next(&self) -> &AtomicU32 {
+        &self.2
+    }
+
+    fn index(&self) -> u32 {
+        self.3
+    }
+}
+
+impl ImplicitAccount for GlobalAccount {
+    fn allocate() -> AccPtr<Self> {
+        match uncache() {
+            Some(account) => unsafe {
+                // SAFETY:
+                // 1.1 Global accounts are leaked
+                AccPtr::new(account)
+            },
+            None => GLOBAL_ALLOCATOR.allocate(),
+        }
+    }
+}
+
+/// Take an account from the current thread's magazine, refilling it if empty.
+///
+/// Returns `None` if the magazine is disabled or already torn down.
+#[inline]
+fn uncache() -> Option<&'static GlobalAccount> {
+    let size = MAGAZINE_SIZE.load(Ordering::Relaxed);
+    if size == 0 {
+        return None;
+    }
+
+    MAGAZINE
+        .try_with(|magazine| {
+            let mut magazine = magazine.borrow_mut();
+            if magazine.0.is_empty() {
+                GLOBAL_ALLOCATOR.allocate_batch(size.div_ceil(2), &mut magazine.0);
+            }
+            magazine.0.pop()
+        })
+        .ok()
+        .flatten()
+}
+
+/// Put a freed account in the current thread's magazine, flushing half of it if full.
+///
+/// Returns `false` if the magazine is disabled or already torn down.
+///
+/// # Safety
+/// Same as [`LockFreeBook::deallocate`]
+#[inline]
+unsafe fn cache(account: &'static GlobalAccount) -> bool {
+    let size = MAGAZINE_SIZE.load(Ordering::Relaxed);
+    if size == 0 {
+        return false;
+    }
+
+    MAGAZINE
+        .try_with(|magazine| {
+            let mut magazine = magazine.borrow_mut();
+            if magazine.0.len() >= size {
+                unsafe {
+                    // SAFETY:
+                    // See `Magazine::drop`
+                    GLOBAL_ALLOCATOR.deallocate_batch(magazine.0.drain(size / 2..));
+                }
+            }
+            magazine.0.push(account);
+        })
+        .is_ok()
+}
+
 #[repr(transparent)]
-struct ParkingLock(parking_lot::RawRwLock);
+pub struct ParkingLock(parking_lot::RawRwLock);
+
+impl ParkingLock {
+    const fn new() -> Self {
+        Self(parking_lot::RawRwLock::INIT)
+    }
+}
 
 impl Default for ParkingLock {
     fn default() -> Self {
-        Self(parking_lot::RawRwLock::INIT)
+        Self::new()
     }
 }
 
@@ -81,6 +305,79 @@ unsafe impl Permits for ParkingLock {
         self.0.try_lock_exclusive()
     }
 
+    fn lock_reference(&self) -> bool {
+        self.0.lock_shared();
+        true
+    }
+
+    fn lock_reference_recursive(&self) -> bool {
+        self.0.lock_shared_recursive();
+        true
+    }
+
+    fn lock_mutation(&self) -> bool {
+        self.0.lock_exclusive();
+        true
+    }
+
+    fn lock_reference_until(&self, deadline: Instant) -> Result<(), WaitError> {
+        if self.0.try_lock_shared_until(deadline) {
+            Ok(())
+        } else {
+            Err(WaitError::TimedOut)
+        }
+    }
+
+    fn lock_mutation_until(&self, deadline: Instant) -> Result<(), WaitError> {
+        if self.0.try_lock_exclusive_until(deadline) {
+            Ok(())
+        } else {
+            Err(WaitError::TimedOut)
+        }
+    }
+
+    unsafe fn duplicate_reference(&self) -> bool {
+        self.0.try_lock_shared_recursive()
+    }
+
+    fn try_upgradable(&self) -> bool {
+        self.0.try_lock_upgradable()
+    }
+
+    fn lock_upgradable(&self) -> bool {
+        self.0.lock_upgradable();
+        true
+    }
+
+    unsafe fn try_upgrade(&self) -> bool {
+        unsafe { self.0.try_upgrade() }
+    }
+
+    unsafe fn upgrade(&self) -> bool {
+        unsafe {
+            self.0.upgrade();
+        }
+        true
+    }
+
+    unsafe fn downgrade_upgradable(&self) {
+        unsafe {
+            self.0.downgrade_upgradable();
+        }
+    }
+
+    unsafe fn relax_to_upgradable(&self) {
+        unsafe {
+            self.0.downgrade_to_upgradable();
+        }
+    }
+
+    unsafe fn abandon_upgradable(&self) {
+        unsafe {
+            self.0.unlock_upgradable();
+        }
+    }
+
     unsafe fn try_escalate(&self) -> bool {
         if self.0.try_lock_upgradable() {
             unsafe {
@@ -112,6 +409,23 @@ unsafe impl Permits for ParkingLock {
         }
     }
 
+    unsafe fn abandon_reference_or_escalate(&self) -> bool {
+        unsafe {
+            self.0.unlock_shared();
+        }
+        self.0.try_lock_exclusive()
+    }
+
+    unsafe fn abandon_upgradable_or_upgrade(&self) -> bool {
+        if unsafe { self.0.try_upgrade() } {
+            return true;
+        }
+        unsafe {
+            self.0.unlock_upgradable();
+        }
+        self.0.try_lock_exclusive()
+    }
+
     unsafe fn abandon_mutation(&self) {
         unsafe {
             self.0.unlock_exclusive();
@@ -0,0 +1,161 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    num::NonZeroU64,
+    ptr::NonNull,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+/// An 8-byte handle to a ralc in a [`SlabAllocator`](crate::SlabAllocator), made of the
+/// index of its account and its generation, the lower 32 bits of half its reallocation
+/// count, which advances by two each time the slot is reused.
+///
+/// Like a [`RalcPtr`](crate::RalcPtr), it goes stale once the ralc is dropped, but it is
+/// resolved against its slab with [`SlabAllocator::get`](crate::SlabAllocator::get) to be
+/// used. Resolving against another slab is safe, but finds unrelated data.
+///
+/// Generations wrap around after 2^32 reuses of a slot, after which a stale handle may
+/// resolve to a newer ralc in the same slot.
+#[repr(transparent)]
+pub struct RalcIndex<T>(NonZeroU64, PhantomData<fn() -> T>);
+
+impl<T> RalcIndex<T> {
+    /// A handle to the ralc at `index` with the even reallocation count `count`.
+    pub(crate) fn new(index: u32, count: u64) -> Self {
+        let bits = ((count >> 1) << 32) | (u64::from(index) + 1);
+        Self(NonZeroU64::new(bits).unwrap(), PhantomData)
+    }
+
+    /// The index of the account in its slab.
+    pub fn index(self) -> u32 {
+        (self.0.get() as u32).wrapping_sub(1)
+    }
+
+    /// The lower 32 bits of half the reallocation count of the ralc.
+    pub fn generation(self) -> u32 {
+        (self.0.get() >> 32) as u32
+    }
+
+    /// The handle as a single non-zero integer, such as to keep in an atomic.
+    pub fn into_bits(self) -> u64 {
+        self.0.get()
+    }
+
+    /// Rebuild a handle from [`RalcIndex::into_bits`]. Fails for zero.
+    pub fn from_bits(bits: u64) -> Option<Self> {
+        NonZeroU64::new(bits).map(|bits| Self(bits, PhantomData))
+    }
+}
+
+impl<T> Clone for RalcIndex<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for RalcIndex<T> {}
+
+impl<T> PartialEq for RalcIndex<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T> Eq for RalcIndex<T> {}
+
+impl<T> Hash for RalcIndex<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for RalcIndex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RalcIndex({}v{})", self.index(), self.generation())
+    }
+}
+
+/// Entries in the first chunk of an [`IndexTable`].
+const FIRST_CHUNK: usize = 64;
+/// Chunks in an [`IndexTable`], enough for all `u32` indexes.
+const CHUNKS: usize = 27;
+
+/// A table of pointers by index, in chunks which double in size so that entries never
+/// move. It can be read while it grows.
+pub(crate) struct IndexTable<X> {
+    chunks: [AtomicPtr<AtomicPtr<X>>; CHUNKS],
+}
+
+impl<X> IndexTable<X> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            chunks: [const { AtomicPtr::new(std::ptr::null_mut()) }; CHUNKS],
+        }
+    }
+
+    /// The chunk and offset of an index.
+    fn locate(index: u32) -> (usize, usize) {
+        let chunk = (index as usize / FIRST_CHUNK + 1).ilog2() as usize;
+        (chunk, index as usize - FIRST_CHUNK * ((1 << chunk) - 1))
+    }
+
+    fn chunk_len(chunk: usize) -> usize {
+        FIRST_CHUNK << chunk
+    }
+
+    /// The pointer inserted at `index`, if any.
+    pub(crate) fn get(&self, index: u32) -> Option<NonNull<X>> {
+        let (chunk, offset) = Self::locate(index);
+        let chunk = NonNull::new(self.chunks[chunk].load(Ordering::Acquire))?;
+        let entry = unsafe {
+            // SAFETY:
+            // 1. Chunks are allocated with their full length and never released before
+            //    the table
+            chunk.add(offset).as_ref()
+        };
+        NonNull::new(entry.load(Ordering::Acquire))
+    }
+
+    /// Insert `ptr` at `index`, growing the table if needed.
+    ///
+    /// # Safety
+    /// 1. Must not be called concurrently on the same table
+    pub(crate) unsafe fn insert(&self, index: u32, ptr: NonNull<X>) {
+        let (chunk, offset) = Self::locate(index);
+        let mut entries = self.chunks[chunk].load(Ordering::Acquire);
+        if entries.is_null() {
+            let fresh = (0..Self::chunk_len(chunk))
+                .map(|_| AtomicPtr::new(std::ptr::null_mut()))
+                .collect::<Box<[_]>>();
+            entries = Box::leak(fresh).as_mut_ptr();
+            // Only this thread grows the table, so there is no race to lose.
+            self.chunks[chunk].store(entries, Ordering::Release);
+        }
+
+        let entry = unsafe {
+            // SAFETY:
+            // 1. Allocated with its full length above or by an earlier insert
+            &*entries.add(offset)
+        };
+        entry.store(ptr.as_ptr(), Ordering::Release);
+    }
+}
+
+impl<X> Drop for IndexTable<X> {
+    fn drop(&mut self) {
+        for (chunk, entries) in self.chunks.iter_mut().enumerate() {
+            let entries = *entries.get_mut();
+            if !entries.is_null() {
+                std::mem::drop(unsafe {
+                    // SAFETY:
+                    // 1. Leaked in `insert` with this length
+                    Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+                        entries,
+                        Self::chunk_len(chunk),
+                    ))
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::{alloc::Layout, ptr::NonNull};
+
+use bumpalo::Bump;
+use ralc_internals::{
+    accounts::{AccPtr, Account},
+    ledger::{FreeListChunk, Ledger, LedgerAllocator},
+};
+
+use crate::ledgers::{CHUNK_SIZE, NewAccount};
+
+/// A free list of accounts, which are allocated in a bump arena that is released
+/// when the book is dropped.
+///
+/// The arena is only released if no account is outstanding at that point.
+/// Otherwise it is leaked, so that handles which outlive the book never
+/// dangle. Books made by [`BumpyBook::leaking`] never release it. The
+/// destructors of accounts are never run.
+pub(crate) struct BumpyBook<N: NewAccount<Account: Account>> {
+    #[cfg(test)]
+    total_allocations: usize,
+    outstanding: usize,
+    leaking: bool,
+    ledger: Ledger<BumpAccounts<N>, CHUNK_SIZE>,
+}
+
+/// Allocates accounts, and the chunks of the free list, from a bump arena.
+struct BumpAccounts<N: NewAccount<Account: Account>> {
+    new_account: N,
+    chunk_size: usize,
+    max_chunk_size: usize,
+    #[cfg(test)]
+    expansions: usize,
+    arena: Option<Bump>,
+    fresh: Option<NonNull<[N::Account]>>,
+}
+
+// SAFETY:
+// 1. Accounts are freshly created, and handed out once each
+// 2. Chunks are allocated from the arena, which is only released when the ledger is dropped
+unsafe impl<N: NewAccount<Account: Account>> LedgerAllocator<CHUNK_SIZE> for BumpAccounts<N> {
+    type Account = N::Account;
+
+    fn allocate_account(&mut self) -> AccPtr<N::Account> {
+        let fresh = match self.fresh {
+            Some(fresh) if !fresh.is_empty() => fresh,
+            _ => {
+                #[cfg(test)]
+                {
+                    self.expansions += 1;
+                }
+
+                let chunk_size = self.chunk_size;
+                self.chunk_size = (self.chunk_size * 2).min(self.max_chunk_size);
+                let arena = self.arena.get_or_insert_with(Bump::new);
+                NonNull::from(
+                    arena.alloc_slice_fill_with(chunk_size, |_| self.new_account.new_account()),
+                )
+            }
+        };
+
+        let account = fresh.cast::<N::Account>();
+        self.fresh = Some(NonNull::slice_from_raw_parts(
+            unsafe {
+                // SAFETY:
+                // 1. At most one past the end of the slice
+                account.add(1)
+            },
+            fresh.len() - 1,
+        ));
+        unsafe {
+            // SAFETY:
+            // 1.2/1.3 The arena is retained for as long as the book, and leaked if any account
+            //         is outstanding then. Which of these applies is up to the owner of the book.
+            AccPtr::new(account.as_ref())
+        }
+    }
+
+    fn allocate_chunk(&mut self) -> NonNull<FreeListChunk<N::Account, CHUNK_SIZE>> {
+        self.arena
+            .get_or_insert_with(Bump::new)
+            .alloc_layout(Layout::new::<FreeListChunk<N::Account, CHUNK_SIZE>>())
+            .cast()
+    }
+
+    unsafe fn deallocate_chunk(&mut self, _chunk: NonNull<FreeListChunk<N::Account, CHUNK_SIZE>>) {
+        // Released together with the arena.
+    }
+}
+
+impl<N: NewAccount<Account: Account>> BumpyBook<N> {
+    pub(crate) const fn new(new_account: N) -> Self {
+        Self {
+            #[cfg(test)]
+            total_allocations: 0,
+            outstanding: 0,
+            leaking: false,
+            ledger: Ledger::new(BumpAccounts {
+                new_account,
+                chunk_size: CHUNK_SIZE,
+                max_chunk_size: CHUNK_SIZE * CHUNK_SIZE,
+                #[cfg(test)]
+                expansions: 0,
+                arena: None,
+                fresh: None,
+            }),
+        }
+    }
+
+    /// A book whose arena is leaked even if no account is outstanding, for accounts
+    /// whose weak pointers may outlive the book.
+    pub(crate) const fn leaking(new_account: N) -> Self {
+        let mut book = Self::new(new_account);
+        book.leaking = true;
+        book
+    }
+
+    /// Pop a free account, or allocate a new chunk of them from the arena.
+    #[inline]
+    pub(crate) fn allocate(&mut self) -> AccPtr<N::Account> {
+        #[cfg(test)]
+        {
+            self.total_allocations += 1;
+        }
+
+        self.outstanding += 1;
+        self.ledger.allocate()
+    }
+
+    /// Return an account to the free list, unless its balance is exhausted.
+    ///
+    /// # Safety
+    /// 1. `account` was returned by [`Self::allocate`] on this book
+    /// 2. `account` is not tracking an allocation and no permits are held on it
+    /// 3. `account` is not deallocated twice
+    #[inline]
+    pub(crate) unsafe fn deallocate(&mut self, account: AccPtr<N::Account>) {
+        self.outstanding -= 1;
+        unsafe {
+            // SAFETY:
+            // Guaranteed by caller
+            self.ledger.deallocate(account)
+        }
+    }
+
+    /// Number of accounts allocated and not yet deallocated.
+    pub(crate) fn outstanding(&self) -> usize {
+        self.outstanding
+    }
+
+    /// Set the size of the next allocated chunk, and the limit it may grow to.
+    pub(crate) fn set_chunks(&mut self, chunk: usize, limit: usize) {
+        let accounts = self.ledger.allocator_mut();
+        accounts.chunk_size = chunk.max(1);
+        accounts.max_chunk_size = limit.max(accounts.chunk_size);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn total_allocations(&self) -> usize {
+        self.total_allocations
+    }
+
+    #[cfg(test)]
+    pub(crate) fn expansions(&self) -> usize {
+        self.ledger.allocator().expansions
+    }
+
+    #[cfg(test)]
+    pub(crate) fn free_count(&self) -> usize {
+        self.ledger.len()
+    }
+
+    /// Clears the free list and any fresh accounts. They are retained, but
+    /// no longer reused.
+    #[cfg(test)]
+    pub(crate) fn reset(&mut self) {
+        self.total_allocations = 0;
+        self.ledger.clear();
+        let accounts = self.ledger.allocator_mut();
+        accounts.expansions = 0;
+        accounts.fresh = None;
+    }
+}
+
+impl<N: NewAccount<Account: Account>> Drop for BumpyBook<N> {
+    fn drop(&mut self) {
+        if self.leaking || self.outstanding > 0 {
+            std::mem::forget(self.ledger.allocator_mut().arena.take());
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use ralc_internals::{
+    accounts::{AccPtr, Account},
+    ledger::{Ledger, LedgerAllocator},
+};
+
+use crate::ledgers::CHUNK_SIZE;
+
+/// A free list of accounts, which are allocated in chunks that are never released.
+pub(crate) struct LeakyBook<A: Account + 'static> {
+    #[cfg(test)]
+    total_allocations: usize,
+    ledger: Ledger<LeakyAccounts<A>, CHUNK_SIZE>,
+}
+
+/// Allocates accounts by leaking chunks of them, each twice as large as the previous.
+struct LeakyAccounts<A: Account + 'static> {
+    new_account: fn() -> A,
+    chunk_size: usize,
+    max_chunk_size: usize,
+    #[cfg(test)]
+    expansions: usize,
+    fresh: &'static [A],
+}
+
+// SAFETY:
+// 1. Accounts are freshly created, and handed out once each
+// 2. Default implementation
+unsafe impl<A: Account + 'static> LedgerAllocator<CHUNK_SIZE> for LeakyAccounts<A> {
+    type Account = A;
+
+    fn allocate_account(&mut self) -> AccPtr<A> {
+        if self.fresh.is_empty() {
+            #[cfg(test)]
+            {
+                self.expansions += 1;
+            }
+
+            self.fresh = (0..self.chunk_size)
+                .map(|_| (self.new_account)())
+                .collect::<Vec<_>>()
+                .leak();
+            self.chunk_size = (self.chunk_size * 2).min(self.max_chunk_size);
+        }
+
+        let (account, fresh) = self.fresh.split_first().unwrap();
+        self.fresh = fresh;
+        unsafe {
+            // SAFETY:
+            // 1.1 Leaked
+            AccPtr::new(account)
+        }
+    }
+}
+
+impl<A: Account + 'static> LeakyBook<A> {
+    pub(crate) const fn new(new_account: fn() -> A) -> Self {
+        Self {
+            #[cfg(test)]
+            total_allocations: 0,
+            ledger: Ledger::new(LeakyAccounts {
+                new_account,
+                chunk_size: CHUNK_SIZE,
+                max_chunk_size: CHUNK_SIZE * CHUNK_SIZE,
+                #[cfg(test)]
+                expansions: 0,
+                fresh: &[],
+            }),
+        }
+    }
+
+    /// Pop a free account, or leak a new chunk of them.
+    #[inline]
+    pub(crate) fn allocate(&mut self) -> AccPtr<A> {
+        #[cfg(test)]
+        {
+            self.total_allocations += 1;
+        }
+
+        self.ledger.allocate()
+    }
+
+    /// Return an account to the free list, unless its balance is exhausted.
+    ///
+    /// # Safety
+    /// 1. `account` was returned by [`Self::allocate`] on this book
+    /// 2. `account` is not tracking an allocation and no permits are held on it
+    /// 3. `account` is not deallocated twice
+    #[inline]
+    pub(crate) unsafe fn deallocate(&mut self, account: AccPtr<A>) {
+        unsafe {
+            // SAFETY:
+            // Guaranteed by caller
+            self.ledger.deallocate(account)
+        }
+    }
+
+    /// Set the size of the next allocated chunk, and the limit it may grow to.
+    pub(crate) fn set_chunks(&mut self, chunk: usize, limit: usize) {
+        let accounts = self.ledger.allocator_mut();
+        accounts.chunk_size = chunk.max(1);
+        accounts.max_chunk_size = limit.max(accounts.chunk_size);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn total_allocations(&self) -> usize {
+        self.total_allocations
+    }
+
+    #[cfg(test)]
+    pub(crate) fn expansions(&self) -> usize {
+        self.ledger.allocator().expansions
+    }
+
+    #[cfg(test)]
+    pub(crate) fn free_count(&self) -> usize {
+        self.ledger.len()
+    }
+
+    /// Clears the free list and any fresh accounts, which are then never reused.
+    #[cfg(test)]
+    pub(crate) fn reset(&mut self) {
+        self.total_allocations = 0;
+        self.ledger.clear();
+        let accounts = self.ledger.allocator_mut();
+        accounts.expansions = 0;
+        accounts.fresh = &[];
+    }
+}
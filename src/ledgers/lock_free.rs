@@ -0,0 +1,379 @@
+use std::{
+    ptr::null_mut,
+    sync::atomic::{AtomicPtr, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+};
+
+use ralc_internals::accounts::{AccPtr, Account};
+
+use crate::ledgers::CHUNK_SIZE;
+
+const OFFSET_BITS: u32 = 20;
+const OFFSET_MASK: u32 = (1 << OFFSET_BITS) - 1;
+const MAX_CHUNK_SIZE: usize = 1 << OFFSET_BITS;
+const MAX_CHUNKS: usize = 1 << (u32::BITS - OFFSET_BITS);
+
+/// An account which can be linked into a [`LockFreeBook`].
+pub(crate) trait Linked: Account + Sync + 'static {
+    /// Create a fresh account at the given index.
+    fn new_linked(index: u32) -> Self;
+
+    /// The index of the next account on the free list, plus one, or zero.
+    fn next(&self) -> &AtomicU32;
+
+    /// The index this account was created at.
+    fn index(&self) -> u32;
+}
+
+/// A free list of accounts which can be shared across threads without locking.
+///
+/// Accounts are allocated in leaked chunks, and addressed by a 32-bit index made from the chunk
+/// number and the offset into the chunk. Fresh accounts are handed out by an atomic bump pointer
+/// into the latest chunk, and freed accounts are pushed onto a Treiber stack whose head is tagged
+/// with a counter to protect against ABA.
+pub(crate) struct LockFreeBook<A: Linked> {
+    /// ABA tag in the high half, index of the top account plus one in the low half.
+    head: AtomicU64,
+    /// Number of chunks in the high half, next fresh offset into the last one in the low half.
+    bump: AtomicU64,
+    chunk_size: AtomicUsize,
+    max_chunk_size: AtomicUsize,
+    chunks: [AtomicPtr<Chunk<A>>; MAX_CHUNKS],
+    #[cfg(test)]
+    total_allocations: AtomicUsize,
+    #[cfg(test)]
+    expansions: AtomicUsize,
+    #[cfg(test)]
+    free_count: AtomicUsize,
+}
+
+struct Chunk<A: 'static> {
+    accounts: &'static [A],
+}
+
+impl<A: Linked> LockFreeBook<A> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            head: AtomicU64::new(0),
+            bump: AtomicU64::new(0),
+            chunk_size: AtomicUsize::new(CHUNK_SIZE),
+            max_chunk_size: AtomicUsize::new(CHUNK_SIZE * CHUNK_SIZE),
+            chunks: [const { AtomicPtr::new(null_mut()) }; MAX_CHUNKS],
+            #[cfg(test)]
+            total_allocations: AtomicUsize::new(0),
+            #[cfg(test)]
+            expansions: AtomicUsize::new(0),
+            #[cfg(test)]
+            free_count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Pop a free account, or take a fresh one, leaking a new chunk if needed.
+    #[inline]
+    pub(crate) fn allocate(&self) -> AccPtr<A> {
+        #[cfg(test)]
+        self.total_allocations.fetch_add(1, Ordering::Relaxed);
+
+        let account = self.pop().unwrap_or_else(|| self.fresh());
+        unsafe {
+            // SAFETY:
+            // 1.1 Chunks are leaked
+            AccPtr::new(account)
+        }
+    }
+
+    /// Return an account to the free list, unless its balance is exhausted.
+    ///
+    /// # Safety
+    /// 1. `account` was returned by [`Self::allocate`] on this book
+    /// 2. `account` is not tracking an allocation and no permits are held on it
+    /// 3. `account` is not deallocated twice
+    #[inline]
+    pub(crate) unsafe fn deallocate(&self, account: &'static A) {
+        if account.exhausted() {
+            return;
+        }
+
+        #[cfg(test)]
+        self.free_count.fetch_add(1, Ordering::Relaxed);
+
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            account.next().store(head as u32, Ordering::Relaxed);
+            let new = (tag(head).wrapping_add(1) << 32) | (account.index() as u64 + 1);
+            match self
+                .head
+                .compare_exchange_weak(head, new, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    /// Move up to `max` accounts from the free list into `into` with a single exchange, or a
+    /// fresh account if the free list is empty. Always moves at least one account.
+    pub(crate) fn allocate_batch(&self, max: usize, into: &mut Vec<&'static A>) {
+        let start = into.len();
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            let top = head as u32;
+            if top == 0 || max == 0 {
+                into.push(self.fresh());
+                break;
+            }
+
+            // As in `pop`, stale links are harmless, since any change to the stack changes the
+            // tag and makes the exchange fail.
+            let mut next = top;
+            while next != 0 && into.len() - start < max {
+                let account = self.get(next - 1);
+                into.push(account);
+                next = account.next().load(Ordering::Relaxed);
+            }
+
+            let new = (tag(head).wrapping_add(1) << 32) | next as u64;
+            match self
+                .head
+                .compare_exchange_weak(head, new, Ordering::Acquire, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    #[cfg(test)]
+                    self.free_count
+                        .fetch_sub(into.len() - start, Ordering::Relaxed);
+                    break;
+                }
+                Err(actual) => {
+                    into.truncate(start);
+                    head = actual;
+                }
+            }
+        }
+
+        #[cfg(test)]
+        self.total_allocations
+            .fetch_add(into.len() - start, Ordering::Relaxed);
+    }
+
+    /// Return a batch of accounts to the free list with a single exchange, retiring those whose
+    /// balance is exhausted.
+    ///
+    /// # Safety
+    /// 1. Every account was returned by [`Self::allocate`] or [`Self::allocate_batch`] on this book
+    /// 2. No account is tracking an allocation and no permits are held on them
+    /// 3. No account is deallocated twice
+    pub(crate) unsafe fn deallocate_batch(&self, accounts: impl IntoIterator<Item = &'static A>) {
+        let mut accounts = accounts.into_iter().filter(|account| !account.exhausted());
+        let Some(bottom) = accounts.next() else {
+            return;
+        };
+
+        // Link the batch into a stack of its own first, so it can be pushed as a whole.
+        let mut top = bottom;
+        #[cfg(test)]
+        let mut count = 1;
+        for account in accounts {
+            account.next().store(top.index() + 1, Ordering::Relaxed);
+            top = account;
+            #[cfg(test)]
+            {
+                count += 1;
+            }
+        }
+
+        #[cfg(test)]
+        self.free_count.fetch_add(count, Ordering::Relaxed);
+
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            bottom.next().store(head as u32, Ordering::Relaxed);
+            let new = (tag(head).wrapping_add(1) << 32) | (top.index() as u64 + 1);
+            match self
+                .head
+                .compare_exchange_weak(head, new, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    /// Set the size of the next allocated chunk, and the limit it may grow to.
+    ///
+    /// Chunks hold at most 2^20 accounts.
+    pub(crate) fn set_chunks(&self, chunk: usize, limit: usize) {
+        let chunk = chunk.clamp(1, MAX_CHUNK_SIZE);
+        self.chunk_size.store(chunk, Ordering::Relaxed);
+        self.max_chunk_size
+            .store(limit.clamp(chunk, MAX_CHUNK_SIZE), Ordering::Relaxed);
+    }
+
+    fn pop(&self) -> Option<&'static A> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            let top = head as u32;
+            if top == 0 {
+                return None;
+            }
+
+            // Accounts are never deallocated, so reading a stale `next` is harmless;
+            // the tag makes the exchange fail if the head was changed in the meantime.
+            let account = self.get(top - 1);
+            let next = account.next().load(Ordering::Relaxed) as u64;
+            let new = (tag(head).wrapping_add(1) << 32) | next;
+            match self
+                .head
+                .compare_exchange_weak(head, new, Ordering::Acquire, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    #[cfg(test)]
+                    self.free_count.fetch_sub(1, Ordering::Relaxed);
+                    return Some(account);
+                }
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    fn fresh(&self) -> &'static A {
+        let mut bump = self.bump.load(Ordering::Acquire);
+        loop {
+            let chunks = (bump >> 32) as usize;
+            let offset = bump as u32;
+
+            let current = chunks.checked_sub(1).map(|c| self.chunk(c));
+            if let Some(current) = current.filter(|c| (offset as usize) < c.accounts.len()) {
+                match self.bump.compare_exchange_weak(
+                    bump,
+                    bump + 1,
+                    Ordering::Acquire,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => return &current.accounts[offset as usize],
+                    Err(actual) => bump = actual,
+                }
+                continue;
+            }
+
+            // The last chunk is used up, so take the first account of the next one.
+            let next = self.open_chunk(chunks);
+            match self.bump.compare_exchange(
+                bump,
+                ((chunks as u64 + 1) << 32) | 1,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return &next.accounts[0],
+                Err(actual) => bump = actual,
+            }
+        }
+    }
+
+    /// Get chunk number `chunk`, leaking it if no other thread did yet.
+    fn open_chunk(&self, chunk: usize) -> &'static Chunk<A> {
+        assert!(chunk < MAX_CHUNKS, "global account table exhausted");
+
+        let existing = self.chunks[chunk].load(Ordering::Acquire);
+        if let Some(existing) = unsafe {
+            // SAFETY:
+            // 1. Chunks are leaked once installed
+            existing.as_ref()
+        } {
+            return existing;
+        }
+
+        let size = self.chunk_size.load(Ordering::Relaxed);
+        let accounts = (0..size)
+            .map(|offset| A::new_linked(((chunk as u32) << OFFSET_BITS) | offset as u32))
+            .collect::<Box<[A]>>();
+        let new = Box::into_raw(Box::new(Chunk {
+            accounts: Box::leak(accounts),
+        }));
+
+        match self.chunks[chunk].compare_exchange(
+            null_mut(),
+            new,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => {
+                #[cfg(test)]
+                self.expansions.fetch_add(1, Ordering::Relaxed);
+
+                let max = self.max_chunk_size.load(Ordering::Relaxed);
+                let _ = self.chunk_size.compare_exchange(
+                    size,
+                    (size * 2).min(max),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                );
+                unsafe {
+                    // SAFETY:
+                    // 1. Just leaked
+                    &*new
+                }
+            }
+            Err(existing) => {
+                std::mem::drop(unsafe {
+                    // SAFETY:
+                    // 1. Never shared, as the exchange failed
+                    let chunk = Box::from_raw(new);
+                    Box::from_raw(std::ptr::from_ref(chunk.accounts).cast_mut())
+                });
+                unsafe {
+                    // SAFETY:
+                    // 1. Chunks are leaked once installed
+                    &*existing
+                }
+            }
+        }
+    }
+
+    fn chunk(&self, chunk: usize) -> &'static Chunk<A> {
+        unsafe {
+            // SAFETY:
+            // 1. Only called for chunks below the bump pointer, which are installed before it
+            //    is advanced past them, and leaked once installed
+            &*self.chunks[chunk].load(Ordering::Acquire)
+        }
+    }
+
+    fn get(&self, index: u32) -> &'static A {
+        &self.chunk((index >> OFFSET_BITS) as usize).accounts[(index & OFFSET_MASK) as usize]
+    }
+
+    #[cfg(test)]
+    pub(crate) fn total_allocations(&self) -> usize {
+        self.total_allocations.load(Ordering::Relaxed)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn expansions(&self) -> usize {
+        self.expansions.load(Ordering::Relaxed)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn free_count(&self) -> usize {
+        self.free_count.load(Ordering::Relaxed)
+    }
+
+    /// Clears the free list and the rest of the current chunk, which are then never reused.
+    ///
+    /// Must not be called concurrently with anything else.
+    #[cfg(test)]
+    pub(crate) fn reset(&self) {
+        self.total_allocations.store(0, Ordering::Relaxed);
+        self.expansions.store(0, Ordering::Relaxed);
+        self.free_count.store(0, Ordering::Relaxed);
+        self.head.store(
+            tag(self.head.load(Ordering::Relaxed)) << 32,
+            Ordering::Relaxed,
+        );
+        let chunks = self.bump.load(Ordering::Relaxed) >> 32;
+        self.bump
+            .store((chunks << 32) | (OFFSET_MASK as u64 + 1), Ordering::Relaxed);
+    }
+}
+
+fn tag(head: u64) -> u64 {
+    head >> 32
+}
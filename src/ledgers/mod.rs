@@ -0,0 +1,38 @@
+#[cfg(feature = "bumpalo")]
+mod bumpy;
+#[cfg(any(feature = "tokio", test))]
+mod leaking;
+#[cfg(feature = "parking-lot")]
+mod lock_free;
+#[cfg(not(feature = "bumpalo"))]
+mod retaining;
+
+#[cfg(feature = "bumpalo")]
+pub(crate) use bumpy::BumpyBook;
+#[cfg(any(feature = "tokio", test))]
+pub(crate) use leaking::LeakyBook;
+#[cfg(feature = "parking-lot")]
+pub(crate) use lock_free::{Linked, LockFreeBook};
+#[cfg(not(feature = "bumpalo"))]
+pub(crate) use retaining::RetainingBook;
+
+#[cfg(not(test))]
+pub(crate) const CHUNK_SIZE: usize = 1024;
+
+#[cfg(test)]
+pub(crate) const CHUNK_SIZE: usize = 16;
+
+/// Construction of fresh accounts for a book.
+pub(crate) trait NewAccount {
+    type Account;
+
+    fn new_account(&self) -> Self::Account;
+}
+
+impl<A> NewAccount for fn() -> A {
+    type Account = A;
+
+    fn new_account(&self) -> A {
+        self()
+    }
+}
@@ -0,0 +1,181 @@
+use std::ptr::NonNull;
+
+use ralc_internals::{
+    accounts::{AccPtr, Account},
+    ledger::{Ledger, LedgerAllocator},
+};
+
+use crate::ledgers::{CHUNK_SIZE, NewAccount};
+
+/// A free list of accounts, which are allocated in chunks that are released
+/// when the book is dropped.
+///
+/// Chunks are only released if no account is outstanding at that point.
+/// Otherwise they are leaked, so that handles which outlive the book never
+/// dangle. Books made by [`RetainingBook::leaking`] never release them.
+pub(crate) struct RetainingBook<N: NewAccount<Account: Account>> {
+    #[cfg(test)]
+    total_allocations: usize,
+    outstanding: usize,
+    leaking: bool,
+    ledger: Ledger<RetainingAccounts<N>, CHUNK_SIZE>,
+}
+
+/// Allocates accounts in chunks, each twice as large as the previous, and
+/// releases them when dropped.
+struct RetainingAccounts<N: NewAccount<Account: Account>> {
+    new_account: N,
+    chunk_size: usize,
+    max_chunk_size: usize,
+    #[cfg(test)]
+    expansions: usize,
+    chunks: Vec<NonNull<[N::Account]>>,
+    fresh: usize,
+}
+
+// SAFETY:
+// 1. Accounts are freshly created, and handed out once each
+// 2. Default implementation
+unsafe impl<N: NewAccount<Account: Account>> LedgerAllocator<CHUNK_SIZE> for RetainingAccounts<N> {
+    type Account = N::Account;
+
+    fn allocate_account(&mut self) -> AccPtr<N::Account> {
+        if self.fresh == 0 {
+            #[cfg(test)]
+            {
+                self.expansions += 1;
+            }
+
+            let chunk = (0..self.chunk_size)
+                .map(|_| self.new_account.new_account())
+                .collect::<Box<[_]>>();
+            self.chunks.push(NonNull::from(Box::leak(chunk)));
+            self.fresh = self.chunk_size;
+            self.chunk_size = (self.chunk_size * 2).min(self.max_chunk_size);
+        }
+
+        let chunk = *self.chunks.last().unwrap();
+        self.fresh -= 1;
+        unsafe {
+            // SAFETY:
+            // 1.2/1.3 Chunks are retained for as long as the book, and leaked if any account
+            //         is outstanding then. Which of these applies is up to the owner of the book.
+            AccPtr::new(chunk.cast::<N::Account>().add(self.fresh).as_ref())
+        }
+    }
+}
+
+impl<N: NewAccount<Account: Account>> Drop for RetainingAccounts<N> {
+    fn drop(&mut self) {
+        for chunk in self.chunks.drain(..) {
+            std::mem::drop(unsafe {
+                // SAFETY:
+                // 1. Leaked in `allocate_account`
+                // 2. Chunks are cleared by `RetainingBook` if any account is outstanding
+                Box::from_raw(chunk.as_ptr())
+            })
+        }
+    }
+}
+
+impl<N: NewAccount<Account: Account>> RetainingBook<N> {
+    pub(crate) const fn new(new_account: N) -> Self {
+        Self {
+            #[cfg(test)]
+            total_allocations: 0,
+            outstanding: 0,
+            leaking: false,
+            ledger: Ledger::new(RetainingAccounts {
+                new_account,
+                chunk_size: CHUNK_SIZE,
+                max_chunk_size: CHUNK_SIZE * CHUNK_SIZE,
+                #[cfg(test)]
+                expansions: 0,
+                chunks: Vec::new(),
+                fresh: 0,
+            }),
+        }
+    }
+
+    /// A book whose chunks are leaked even if no account is outstanding, for accounts
+    /// whose weak pointers may outlive the book.
+    pub(crate) const fn leaking(new_account: N) -> Self {
+        let mut book = Self::new(new_account);
+        book.leaking = true;
+        book
+    }
+
+    /// Pop a free account, or allocate a new chunk of them.
+    #[inline]
+    pub(crate) fn allocate(&mut self) -> AccPtr<N::Account> {
+        #[cfg(test)]
+        {
+            self.total_allocations += 1;
+        }
+
+        self.outstanding += 1;
+        self.ledger.allocate()
+    }
+
+    /// Return an account to the free list, unless its balance is exhausted.
+    ///
+    /// # Safety
+    /// 1. `account` was returned by [`Self::allocate`] on this book
+    /// 2. `account` is not tracking an allocation and no permits are held on it
+    /// 3. `account` is not deallocated twice
+    #[inline]
+    pub(crate) unsafe fn deallocate(&mut self, account: AccPtr<N::Account>) {
+        self.outstanding -= 1;
+        unsafe {
+            // SAFETY:
+            // Guaranteed by caller
+            self.ledger.deallocate(account)
+        }
+    }
+
+    /// Number of accounts allocated and not yet deallocated.
+    pub(crate) fn outstanding(&self) -> usize {
+        self.outstanding
+    }
+
+    /// Set the size of the next allocated chunk, and the limit it may grow to.
+    pub(crate) fn set_chunks(&mut self, chunk: usize, limit: usize) {
+        let accounts = self.ledger.allocator_mut();
+        accounts.chunk_size = chunk.max(1);
+        accounts.max_chunk_size = limit.max(accounts.chunk_size);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn total_allocations(&self) -> usize {
+        self.total_allocations
+    }
+
+    #[cfg(test)]
+    pub(crate) fn expansions(&self) -> usize {
+        self.ledger.allocator().expansions
+    }
+
+    #[cfg(test)]
+    pub(crate) fn free_count(&self) -> usize {
+        self.ledger.len()
+    }
+
+    /// Clears the free list and any fresh accounts. They are retained, but
+    /// no longer reused.
+    #[cfg(test)]
+    pub(crate) fn reset(&mut self) {
+        self.total_allocations = 0;
+        self.ledger.clear();
+        let accounts = self.ledger.allocator_mut();
+        accounts.expansions = 0;
+        accounts.fresh = 0;
+    }
+}
+
+impl<N: NewAccount<Account: Account>> Drop for RetainingBook<N> {
+    fn drop(&mut self) {
+        if self.leaking || self.outstanding > 0 {
+            self.ledger.allocator_mut().chunks.clear();
+        }
+    }
+}
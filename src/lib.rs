@@ -1,18 +1,219 @@
-use ralc_internals::{RalcRaw, accounts::Account, declare_marker_type, marker::Marker};
+#![cfg_attr(feature = "nightly", feature(coerce_unsized, unsize))]
 
-mod bumpallo_ledger;
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    time::{Duration, Instant},
+};
+
+pub use ralc_internals::alloc;
+use ralc_internals::{
+    RalcRaw,
+    accounts::{AccPtr, Account, permits::WaitError},
+    alloc::{Allocator, Payload},
+    declare_marker_type,
+    marker::Marker,
+};
+
+use crate::deadlock::{Held, Token};
+#[cfg(feature = "nightly")]
+use std::{marker::Unsize, ops::CoerceUnsized};
+
+mod deadlock;
+#[cfg(feature = "parking-lot")]
 mod global;
+mod index;
 mod ledgers;
+mod mapped;
+mod pool;
+mod slab;
+#[cfg(any(feature = "tokio", test))]
+mod task_local;
+#[cfg(test)]
+mod test;
+mod thread_local;
+mod waiting;
+
+pub use deadlock::AccountId;
+#[cfg(feature = "parking-lot")]
+pub use global::{Global, GlobalAccount, GlobalAllocator};
+pub use index::RalcIndex;
+pub use mapped::{Field, MappedRalcMut, MappedRalcPtr, MappedRalcRef, SplitRalcMut};
+pub use pool::{LocalPool, PoolAccount, PoolAllocator, SyncPool};
+pub use slab::{LocalSlab, SlabAccount, SlabAllocator, SyncSlab};
+#[cfg(any(feature = "tokio", test))]
+pub use task_local::{FutureExt, TaskLocal, TaskLocalAccount, TaskLocalAllocator};
+pub use thread_local::{ThreadLocal, ThreadLocalAccount, ThreadLocalAllocator};
+pub use waiting::{ReadAsync, WriteAsync};
 
 declare_marker_type!(Boxed, 1);
 declare_marker_type!(Mutable, 2);
 declare_marker_type!(Reference, 3);
 declare_marker_type!(Pointer, 4);
+declare_marker_type!(Upgradable, 5);
+
+// Every handle is as small as its `Option`, whether the data pointer is kept in the handle
+// or in the account.
+const _: () = {
+    macro_rules! assert_niche {
+        ($($handle:ty),* $(,)?) => {
+            $(assert!(size_of::<Option<$handle>>() == size_of::<$handle>());)*
+        };
+    }
+
+    type Slab = SlabAccount<'static, u8, std::cell::Cell<u64>, std::cell::Cell<u32>>;
+    type InPlace = alloc::InPlace;
+    assert_niche!(
+        RalcBox<u8, ThreadLocal>,
+        RalcMut<u8, ThreadLocal>,
+        RalcRef<u8, ThreadLocal>,
+        RalcUpgradable<u8, ThreadLocal>,
+        RalcPtr<u8, ThreadLocal>,
+        RalcBox<[u8], ThreadLocal>,
+        RalcPtr<[u8], ThreadLocal>,
+        RalcBox<u8, Slab, InPlace>,
+        RalcMut<u8, Slab, InPlace>,
+        RalcRef<u8, Slab, InPlace>,
+        RalcUpgradable<u8, Slab, InPlace>,
+        RalcPtr<u8, Slab, InPlace>,
+        MappedRalcRef<[u8], u8, ThreadLocal>,
+        MappedRalcMut<[u8], u8, ThreadLocal>,
+        MappedRalcPtr<[u8], u8, ThreadLocal>,
+        SplitRalcMut<[u8], u8, ThreadLocal>,
+        RalcIndex<u8>,
+    );
+};
+
+/// Why access to the data of a ralc was not granted.
+///
+/// Errors compare equal by their kind alone, ignoring any guard they carry.
+#[derive(Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum NoAccess<G = ()> {
+    /// Reference is currently unavailable due to an active lock.
+    Blocked,
+    /// Reference is stale.
+    Stale,
+    /// Reference is currently unavailable due to an active lock,
+    /// and a waiting operation was requested which would deadlock.
+    ///
+    /// Carries the account if the current thread holds the lock itself, as
+    /// detected with the `deadlock-detection` feature.
+    Deadlock(Option<AccountId>),
+    /// Reference was still unavailable due to an active lock when
+    /// a timed waiting operation gave up.
+    Timeout,
+    /// Reference is currently unavailable due to an active lock, and a waiting
+    /// operation was requested which the account does not support.
+    Unsupported,
+    /// Access was granted, but a writer panicked while holding it earlier, so
+    /// the data may be inconsistent. The guard can still be used to recover it.
+    Poisoned(G),
+}
+
+impl<G> NoAccess<G> {
+    /// Recover the guard of a [`NoAccess::Poisoned`] error.
+    pub fn into_poisoned(self) -> Option<G> {
+        match self {
+            NoAccess::Poisoned(guard) => Some(guard),
+            _ => None,
+        }
+    }
+
+    /// Apply `f` to the guard of a [`NoAccess::Poisoned`] error.
+    pub fn map_poisoned<H>(self, f: impl FnOnce(G) -> H) -> NoAccess<H> {
+        match self {
+            NoAccess::Blocked => NoAccess::Blocked,
+            NoAccess::Stale => NoAccess::Stale,
+            NoAccess::Deadlock(account) => NoAccess::Deadlock(account),
+            NoAccess::Timeout => NoAccess::Timeout,
+            NoAccess::Unsupported => NoAccess::Unsupported,
+            NoAccess::Poisoned(guard) => NoAccess::Poisoned(f(guard)),
+        }
+    }
+}
+
+impl<G> PartialEq for NoAccess<G> {
+    fn eq(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+impl<G> Eq for NoAccess<G> {}
+
+impl<G> Hash for NoAccess<G> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+    }
+}
+
+impl<G> From<WaitError> for NoAccess<G> {
+    fn from(value: WaitError) -> Self {
+        match value {
+            WaitError::TimedOut => NoAccess::Timeout,
+            WaitError::Unsupported => NoAccess::Unsupported,
+        }
+    }
+}
+
+/// The result of acquiring access, which can still hand out the guard `G` if poisoned.
+pub type Result<T, G = T> = std::result::Result<T, NoAccess<G>>;
+
+/// The deadline `timeout` from now, or the farthest representable one.
+fn deadline_after(mut timeout: Duration) -> Instant {
+    let now = Instant::now();
+    loop {
+        if let Some(deadline) = now.checked_add(timeout) {
+            return deadline;
+        }
+        timeout /= 2;
+    }
+}
+
+/// Hand out `guard`, unless the allocation was poisoned.
+fn unpoisoned<G, A: Account>(account: &A, guard: G) -> Result<G> {
+    if account.is_poisoned() {
+        Err(NoAccess::Poisoned(guard))
+    } else {
+        Ok(guard)
+    }
+}
+
+/// An [`Account`] type with an implicit allocator, such that ralcs
+/// tracked by it can be created with [`RalcBox::new`].
+pub trait ImplicitAccount: Account {
+    /// Allocate an unused, non-exhausted account with no permits held.
+    fn allocate() -> AccPtr<Self>;
+}
 
+/// The owning reallocation-counting pointer.
+///
+/// Dropping it invalidates all [`RalcPtr`]s borrowed from it. The data itself is
+/// dropped once the last [`RalcRef`] or [`RalcMut`] is.
 #[repr(transparent)]
-pub struct RalcBox<T, A: Account>(RalcRaw<A, Boxed, T>);
+pub struct RalcBox<T: ?Sized, A: Account, Alloc: Allocator = alloc::Global>(
+    RalcRaw<A, Boxed, T, Alloc>,
+);
+
+unsafe impl<T: ?Sized + Send + Sync, A: Account + Sync, Alloc: Allocator + Send + Sync> Send
+    for RalcBox<T, A, Alloc>
+{
+}
+unsafe impl<T: ?Sized + Send + Sync, A: Account + Sync, Alloc: Allocator + Send + Sync> Sync
+    for RalcBox<T, A, Alloc>
+{
+}
+#[cfg(feature = "nightly")]
+impl<T: ?Sized + Unsize<U>, U: ?Sized, A: Account, Alloc: Allocator>
+    CoerceUnsized<RalcBox<U, A, Alloc>> for RalcBox<T, A, Alloc>
+where
+    RalcRaw<A, Boxed, T, Alloc>: CoerceUnsized<RalcRaw<A, Boxed, U, Alloc>>,
+{
+}
 
-impl<T, A: Account> Drop for RalcBox<T, A> {
+impl<T: ?Sized, A: Account, Alloc: Allocator> Drop for RalcBox<T, A, Alloc> {
     fn drop(&mut self) {
         unsafe {
             // SAFETY:
@@ -22,11 +223,300 @@ impl<T, A: Account> Drop for RalcBox<T, A> {
     }
 }
 
-#[repr(transparent)]
-pub struct RalcMut<T, A: Account>(RalcRaw<A, Mutable, T>);
+impl<T, A: ImplicitAccount> RalcBox<T, A> {
+    /// Allocate the data with the global allocator, which with the `recycling` feature
+    /// reuses memory of ralcs freed on this thread.
+    pub fn new(value: T) -> Self {
+        Self::new_in(value, alloc::Global)
+    }
+}
+
+impl<T, A: ImplicitAccount, Alloc: Allocator> RalcBox<T, A, Alloc> {
+    /// Allocate the data with `alloc`, which frees it once the last pointer to it is gone.
+    pub fn new_in(value: T, alloc: Alloc) -> Self {
+        unsafe {
+            // SAFETY:
+            // 1. Freshly allocated
+            // 2. Guaranteed by `ImplicitAccount::allocate`
+            Self::from_parts_in(A::allocate(), value, alloc)
+        }
+    }
+}
+
+impl<T: ?Sized, A: ImplicitAccount> RalcBox<T, A> {
+    /// Take over an existing box, which may hold a slice, string or trait object.
+    pub fn from_box(data: Box<T>) -> Self {
+        unsafe {
+            // SAFETY:
+            // 1. Freshly allocated
+            // 2. Guaranteed by `ImplicitAccount::allocate`
+            Self::from_parts(A::allocate(), data)
+        }
+    }
+}
+
+impl<T: ?Sized, A: Account> RalcBox<T, A> {
+    /// # Safety
+    /// 1. The account must not be tracking any other allocation
+    /// 2. The account must not be exhausted and no permits may be held on it
+    pub(crate) unsafe fn from_parts(account: AccPtr<A>, data: Box<T>) -> Self {
+        Self(unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            // 2. Guaranteed by caller
+            RalcRaw::from_parts(account, data)
+        })
+    }
+
+    /// Take back the data, invalidating all weak pointers. Fails if any
+    /// readers or writers are active.
+    pub fn try_into_box(self) -> std::result::Result<Box<T>, Self> {
+        let raw = unsafe {
+            // SAFETY:
+            // 1. "owned" state, self is forgotten below if successful
+            self.0.disown_into_mut()
+        };
+        let Some(raw) = raw else {
+            return Err(self);
+        };
+        std::mem::forget(self);
+
+        Ok(unsafe {
+            // SAFETY:
+            // 1. Acquired and disowned just above
+            // 2. Disowned just above
+            raw.free_into_box()
+        })
+    }
+}
+
+impl<T, A: Account, Alloc: Allocator> RalcBox<T, A, Alloc> {
+    /// # Safety
+    /// 1. The account must not be tracking any other allocation
+    /// 2. The account must not be exhausted and no permits may be held on it
+    pub(crate) unsafe fn from_parts_in(account: AccPtr<A>, data: T, alloc: Alloc) -> Self {
+        Self(unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            // 2. Guaranteed by caller
+            RalcRaw::from_parts_in(account, data, alloc)
+        })
+    }
+
+    /// Take back the data, invalidating all weak pointers. Fails if any
+    /// readers or writers are active.
+    pub fn try_into_inner(self) -> std::result::Result<T, Self> {
+        let raw = unsafe {
+            // SAFETY:
+            // 1. "owned" state, self is forgotten below if successful
+            self.0.disown_into_mut()
+        };
+        let Some(raw) = raw else {
+            return Err(self);
+        };
+        std::mem::forget(self);
+
+        Ok(unsafe {
+            // SAFETY:
+            // 1. Acquired and disowned just above
+            // 2. Disowned just above
+            raw.free_into_inner()
+        })
+    }
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> RalcBox<T, A, Alloc> {
+    /// # Safety
+    /// 1. The account must not be tracking any other allocation
+    /// 2. The account must not be exhausted and no permits may be held on it
+    /// 3. `data` must be a live payload, which is only used through this ralc until freed
+    pub(crate) unsafe fn from_payload(
+        account: AccPtr<A>,
+        data: NonNull<Payload<Alloc, T>>,
+    ) -> Self {
+        Self(unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            // 2. Guaranteed by caller
+            // 3. Guaranteed by caller
+            RalcRaw::from_payload(account, data)
+        })
+    }
+
+    #[cfg(test)]
+    pub(crate) fn account(&self) -> &A {
+        self.0.account()
+    }
+
+    /// Identify the account tracking this allocation.
+    pub fn account_id(&self) -> AccountId {
+        AccountId::of(self.0.account())
+    }
+
+    /// The reallocation count of the account while it tracks this allocation, which tells
+    /// it apart from other allocations tracked by the same account.
+    pub fn generation(&self) -> u64 {
+        self.0.generation()
+    }
+
+    /// Get a weak pointer to this allocation.
+    pub fn borrow(&self) -> RalcPtr<T, A, Alloc> {
+        RalcPtr(self.0.switch_marker())
+    }
+
+    /// Get a readable reference. Returns immediately if access cannot be acquired.
+    pub fn try_read(&self) -> Result<RalcRef<T, A, Alloc>> {
+        let Some(raw) = self.0.try_acquire_ref() else {
+            return Err(NoAccess::Blocked);
+        };
+        unpoisoned(self.0.account(), RalcRef::new(raw.switch_marker()))
+    }
+
+    /// Get a writable reference. Returns immediately if access cannot be acquired.
+    pub fn try_write(&self) -> Result<RalcMut<T, A, Alloc>> {
+        let Some(raw) = self.0.try_acquire_mut() else {
+            return Err(NoAccess::Blocked);
+        };
+        unpoisoned(self.0.account(), RalcMut::new(raw.switch_marker()))
+    }
+
+    /// Get an upgradable reference. Returns immediately if access cannot be acquired.
+    pub fn try_upgradable_read(&self) -> Result<RalcUpgradable<T, A, Alloc>> {
+        let Some(raw) = self.0.try_acquire_upgradable() else {
+            return Err(NoAccess::Blocked);
+        };
+        unpoisoned(self.0.account(), RalcUpgradable::new(raw.switch_marker()))
+    }
+
+    /// Get an upgradable reference. Waits for access if the account supports it, and
+    /// fails with [`NoAccess::Deadlock`] otherwise.
+    pub fn upgradable_read(&self) -> Result<RalcUpgradable<T, A, Alloc>> {
+        deadlock::check(self.account_id(), Held::Upgradable)?;
+        let Some(raw) = self.0.acquire_upgradable() else {
+            return Err(NoAccess::Deadlock(None));
+        };
+        unpoisoned(self.0.account(), RalcUpgradable::new(raw.switch_marker()))
+    }
+
+    /// Get a readable reference. Waits for access if the account supports it, and
+    /// fails with [`NoAccess::Deadlock`] otherwise.
+    pub fn read(&self) -> Result<RalcRef<T, A, Alloc>> {
+        deadlock::check(self.account_id(), Held::Reference)?;
+        let raw = if deadlock::holds_reference(self.account_id()) {
+            self.0.acquire_ref_recursive()
+        } else {
+            self.0.acquire_ref()
+        };
+        let Some(raw) = raw else {
+            return Err(NoAccess::Deadlock(None));
+        };
+        unpoisoned(self.0.account(), RalcRef::new(raw.switch_marker()))
+    }
+
+    /// Get a writable reference. Waits for access if the account supports it, and
+    /// fails with [`NoAccess::Deadlock`] otherwise.
+    pub fn write(&self) -> Result<RalcMut<T, A, Alloc>> {
+        deadlock::check(self.account_id(), Held::Mutation)?;
+        let Some(raw) = self.0.acquire_mut() else {
+            return Err(NoAccess::Deadlock(None));
+        };
+        unpoisoned(self.0.account(), RalcMut::new(raw.switch_marker()))
+    }
+
+    /// Get a readable reference, waiting at most `timeout` for access. Fails with
+    /// [`NoAccess::Timeout`] if it runs out, or [`NoAccess::Unsupported`] if the account
+    /// does not support waiting.
+    pub fn try_read_for(&self, timeout: Duration) -> Result<RalcRef<T, A, Alloc>> {
+        self.try_read_until(deadline_after(timeout))
+    }
+
+    /// Get a writable reference, waiting at most `timeout` for access. Fails with
+    /// [`NoAccess::Timeout`] if it runs out, or [`NoAccess::Unsupported`] if the account
+    /// does not support waiting.
+    pub fn try_write_for(&self, timeout: Duration) -> Result<RalcMut<T, A, Alloc>> {
+        self.try_write_until(deadline_after(timeout))
+    }
+
+    /// Get a readable reference, waiting until `deadline` for access. Fails with
+    /// [`NoAccess::Timeout`] if it passes, or [`NoAccess::Unsupported`] if the account
+    /// does not support waiting.
+    pub fn try_read_until(&self, deadline: Instant) -> Result<RalcRef<T, A, Alloc>> {
+        let raw = self.0.acquire_ref_until(deadline)?;
+        unpoisoned(self.0.account(), RalcRef::new(raw.switch_marker()))
+    }
+
+    /// Get a writable reference, waiting until `deadline` for access. Fails with
+    /// [`NoAccess::Timeout`] if it passes, or [`NoAccess::Unsupported`] if the account
+    /// does not support waiting.
+    pub fn try_write_until(&self, deadline: Instant) -> Result<RalcMut<T, A, Alloc>> {
+        let raw = self.0.acquire_mut_until(deadline)?;
+        unpoisoned(self.0.account(), RalcMut::new(raw.switch_marker()))
+    }
+
+    /// Check whether a writer panicked while holding access to the data.
+    pub fn is_poisoned(&self) -> bool {
+        self.0.account().is_poisoned()
+    }
+
+    /// Mark the data as recovered from a panicking writer.
+    pub fn clear_poison(&self) {
+        self.0.account().clear_poison();
+    }
+}
+
+impl<T: fmt::Debug + ?Sized, A: Account, Alloc: Allocator> fmt::Debug for RalcBox<T, A, Alloc> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let data = self.try_read();
+        let name = std::any::type_name::<T>();
+        f.debug_tuple("RalcBox")
+            .field(match &data.as_deref() {
+                Ok(data) => data,
+                Err(_) => &name,
+            })
+            .finish()
+    }
+}
+
+impl<T: fmt::Display + ?Sized, A: Account, Alloc: Allocator> fmt::Display for RalcBox<T, A, Alloc> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let marker = if f.alternate() { " (owned)" } else { "" };
+        if let Ok(r) = self.try_read() {
+            write!(f, "{}{}", r, marker)
+        } else {
+            write!(f, "<unavailable>{}", marker)
+        }
+    }
+}
 
-impl<T, A: Account> Drop for RalcMut<T, A> {
+/// Exclusive access to the data of a ralc.
+#[cfg_attr(not(feature = "deadlock-detection"), repr(transparent))]
+pub struct RalcMut<T: ?Sized, A: Account, Alloc: Allocator = alloc::Global>(
+    RalcRaw<A, Mutable, T, Alloc>,
+    Token,
+);
+
+unsafe impl<T: ?Sized + Send + Sync, A: Account + Sync, Alloc: Allocator + Send + Sync> Send
+    for RalcMut<T, A, Alloc>
+{
+}
+unsafe impl<T: ?Sized + Send + Sync, A: Account + Sync, Alloc: Allocator + Send + Sync> Sync
+    for RalcMut<T, A, Alloc>
+{
+}
+#[cfg(feature = "nightly")]
+impl<T: ?Sized + Unsize<U>, U: ?Sized, A: Account, Alloc: Allocator>
+    CoerceUnsized<RalcMut<U, A, Alloc>> for RalcMut<T, A, Alloc>
+where
+    RalcRaw<A, Mutable, T, Alloc>: CoerceUnsized<RalcRaw<A, Mutable, U, Alloc>>,
+{
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> Drop for RalcMut<T, A, Alloc> {
     fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.0.account().poison();
+        }
+        deadlock::released(AccountId::of(self.0.account()), &self.1);
         unsafe {
             // SAFETY:
             // Invariant
@@ -35,11 +525,103 @@ impl<T, A: Account> Drop for RalcMut<T, A> {
     }
 }
 
-#[repr(transparent)]
-pub struct RalcRef<T, A: Account>(RalcRaw<A, Reference, T>);
+impl<T: ?Sized, A: Account, Alloc: Allocator> RalcMut<T, A, Alloc> {
+    fn new(raw: RalcRaw<A, Mutable, T, Alloc>) -> Self {
+        let token = deadlock::acquired(AccountId::of(raw.account()), Held::Mutation);
+        Self(raw, token)
+    }
+
+    /// Relinquish exclusive access, retaining shared access.
+    pub fn into_read(self) -> RalcRef<T, A, Alloc> {
+        let raw = self.0;
+        deadlock::released(AccountId::of(self.0.account()), &self.1);
+        std::mem::forget(self);
+        RalcRef::new(
+            unsafe {
+                // SAFETY:
+                // 1. "writing" state, self is forgotten above
+                raw.downgrade_mut_into_ref()
+            }
+            .switch_marker(),
+        )
+    }
+
+    /// Relinquish exclusive access, retaining shared access which may be upgraded again.
+    pub fn into_upgradable(self) -> RalcUpgradable<T, A, Alloc> {
+        let raw = self.0;
+        deadlock::released(AccountId::of(self.0.account()), &self.1);
+        std::mem::forget(self);
+        RalcUpgradable::new(
+            unsafe {
+                // SAFETY:
+                // 1. "writing" state, self is forgotten above
+                raw.downgrade_mut_into_upgradable()
+            }
+            .switch_marker(),
+        )
+    }
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> Deref for RalcMut<T, A, Alloc> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            // SAFETY:
+            // 1. The mutation permit is held
+            self.0.data().as_ref()
+        }
+    }
+}
 
-impl<T, A: Account> Drop for RalcRef<T, A> {
+impl<T: ?Sized, A: Account, Alloc: Allocator> DerefMut for RalcMut<T, A, Alloc> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe {
+            // SAFETY:
+            // 1. The mutation permit is held
+            self.0.data().as_mut()
+        }
+    }
+}
+
+impl<T: fmt::Debug + ?Sized, A: Account, Alloc: Allocator> fmt::Debug for RalcMut<T, A, Alloc> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.deref(), f)
+    }
+}
+
+impl<T: fmt::Display + ?Sized, A: Account, Alloc: Allocator> fmt::Display for RalcMut<T, A, Alloc> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.deref(), f)
+    }
+}
+
+/// Shared access to the data of a ralc.
+#[cfg_attr(not(feature = "deadlock-detection"), repr(transparent))]
+pub struct RalcRef<T: ?Sized, A: Account, Alloc: Allocator = alloc::Global>(
+    RalcRaw<A, Reference, T, Alloc>,
+    Token,
+);
+
+unsafe impl<T: ?Sized + Send + Sync, A: Account + Sync, Alloc: Allocator + Send + Sync> Send
+    for RalcRef<T, A, Alloc>
+{
+}
+unsafe impl<T: ?Sized + Send + Sync, A: Account + Sync, Alloc: Allocator + Send + Sync> Sync
+    for RalcRef<T, A, Alloc>
+{
+}
+#[cfg(feature = "nightly")]
+impl<T: ?Sized + Unsize<U>, U: ?Sized, A: Account, Alloc: Allocator>
+    CoerceUnsized<RalcRef<U, A, Alloc>> for RalcRef<T, A, Alloc>
+where
+    RalcRaw<A, Reference, T, Alloc>: CoerceUnsized<RalcRaw<A, Reference, U, Alloc>>,
+{
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> Drop for RalcRef<T, A, Alloc> {
     fn drop(&mut self) {
+        deadlock::released(AccountId::of(self.0.account()), &self.1);
         unsafe {
             // SAFETY:
             // Invariant
@@ -48,16 +630,483 @@ impl<T, A: Account> Drop for RalcRef<T, A> {
     }
 }
 
-impl<T, A: Account> Clone for RalcRef<T, A> {
+impl<T: ?Sized, A: Account, Alloc: Allocator> Clone for RalcRef<T, A, Alloc> {
     fn clone(&self) -> Self {
+        Self::new(unsafe {
+            // SAFETY:
+            // Invariant
+            self.0.clone_ref()
+        })
+    }
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> RalcRef<T, A, Alloc> {
+    fn new(raw: RalcRaw<A, Reference, T, Alloc>) -> Self {
+        let token = deadlock::acquired(AccountId::of(raw.account()), Held::Reference);
+        Self(raw, token)
+    }
+
+    /// Attempt to gain exclusive access, which succeeds only if
+    /// this is the only reader.
+    pub fn try_into_write(self) -> std::result::Result<RalcMut<T, A, Alloc>, Self> {
+        let upgraded = unsafe {
+            // SAFETY:
+            // 1. "reading" state, self is forgotten below if successful
+            self.0.try_upgrade_ref_into_mut()
+        };
+        let Some(raw) = upgraded else {
+            return Err(self);
+        };
+        deadlock::released(AccountId::of(self.0.account()), &self.1);
+        std::mem::forget(self);
+        Ok(RalcMut::new(raw.switch_marker()))
+    }
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> Deref for RalcRef<T, A, Alloc> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            // SAFETY:
+            // 1. A reference permit is held
+            self.0.data().as_ref()
+        }
+    }
+}
+
+impl<T: fmt::Debug + ?Sized, A: Account, Alloc: Allocator> fmt::Debug for RalcRef<T, A, Alloc> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.deref(), f)
+    }
+}
+
+impl<T: fmt::Display + ?Sized, A: Account, Alloc: Allocator> fmt::Display for RalcRef<T, A, Alloc> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.deref(), f)
+    }
+}
+
+/// Shared access to the data of a ralc, which may be upgraded to exclusive access
+/// without letting go of it.
+///
+/// It coexists with [`RalcRef`]s, but only one may exist at a time per ralc, and
+/// never alongside a [`RalcMut`].
+#[cfg_attr(not(feature = "deadlock-detection"), repr(transparent))]
+pub struct RalcUpgradable<T: ?Sized, A: Account, Alloc: Allocator = alloc::Global>(
+    RalcRaw<A, Upgradable, T, Alloc>,
+    Token,
+);
+
+unsafe impl<T: ?Sized + Send + Sync, A: Account + Sync, Alloc: Allocator + Send + Sync> Send
+    for RalcUpgradable<T, A, Alloc>
+{
+}
+unsafe impl<T: ?Sized + Send + Sync, A: Account + Sync, Alloc: Allocator + Send + Sync> Sync
+    for RalcUpgradable<T, A, Alloc>
+{
+}
+#[cfg(feature = "nightly")]
+impl<T: ?Sized + Unsize<U>, U: ?Sized, A: Account, Alloc: Allocator>
+    CoerceUnsized<RalcUpgradable<U, A, Alloc>> for RalcUpgradable<T, A, Alloc>
+where
+    RalcRaw<A, Upgradable, T, Alloc>: CoerceUnsized<RalcRaw<A, Upgradable, U, Alloc>>,
+{
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> Drop for RalcUpgradable<T, A, Alloc> {
+    fn drop(&mut self) {
+        deadlock::released(AccountId::of(self.0.account()), &self.1);
         unsafe {
             // SAFETY:
             // Invariant
-            self.0.clone_ref();
+            self.0.drop_upgradable();
+        }
+    }
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> RalcUpgradable<T, A, Alloc> {
+    fn new(raw: RalcRaw<A, Upgradable, T, Alloc>) -> Self {
+        let token = deadlock::acquired(AccountId::of(raw.account()), Held::Upgradable);
+        Self(raw, token)
+    }
+
+    /// Attempt to gain exclusive access, which succeeds only if there are no readers.
+    pub fn try_into_write(self) -> std::result::Result<RalcMut<T, A, Alloc>, Self> {
+        let upgraded = unsafe {
+            // SAFETY:
+            // 1. "upgradable" state, self is forgotten below if successful
+            self.0.try_upgrade_upgradable_into_mut()
+        };
+        let Some(raw) = upgraded else {
+            return Err(self);
+        };
+        deadlock::released(AccountId::of(self.0.account()), &self.1);
+        std::mem::forget(self);
+        Ok(RalcMut::new(raw.switch_marker()))
+    }
+
+    /// Gain exclusive access, waiting for all readers to leave if the account supports it.
+    /// Fails if there are readers and waiting is not supported, or if the current thread
+    /// holds one of them, as detected with the `deadlock-detection` feature.
+    pub fn into_write(self) -> std::result::Result<RalcMut<T, A, Alloc>, Self> {
+        if deadlock::check_upgrade(AccountId::of(self.0.account())).is_err() {
+            return Err(self);
+        }
+        let upgraded = unsafe {
+            // SAFETY:
+            // 1. "upgradable" state, self is forgotten below if successful
+            self.0.upgrade_upgradable_into_mut()
+        };
+        let Some(raw) = upgraded else {
+            return Err(self);
+        };
+        deadlock::released(AccountId::of(self.0.account()), &self.1);
+        std::mem::forget(self);
+        Ok(RalcMut::new(raw.switch_marker()))
+    }
+
+    /// Give up the option to upgrade, retaining shared access.
+    pub fn into_read(self) -> RalcRef<T, A, Alloc> {
+        let raw = self.0;
+        deadlock::released(AccountId::of(self.0.account()), &self.1);
+        std::mem::forget(self);
+        RalcRef::new(
+            unsafe {
+                // SAFETY:
+                // 1. "upgradable" state, self is forgotten above
+                raw.downgrade_upgradable_into_ref()
+            }
+            .switch_marker(),
+        )
+    }
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> Deref for RalcUpgradable<T, A, Alloc> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            // SAFETY:
+            // 1. The upgradable permit is held
+            self.0.data().as_ref()
         }
-        Self(self.0)
     }
 }
 
+impl<T: fmt::Debug + ?Sized, A: Account, Alloc: Allocator> fmt::Debug
+    for RalcUpgradable<T, A, Alloc>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.deref(), f)
+    }
+}
+
+impl<T: fmt::Display + ?Sized, A: Account, Alloc: Allocator> fmt::Display
+    for RalcUpgradable<T, A, Alloc>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.deref(), f)
+    }
+}
+
+/// A weak reallocation-counting pointer.
+///
+/// Unlike `Arc::Weak`, this is `Copy` and has no `Drop`.
 #[repr(transparent)]
-pub struct RalcPtr<T, A: Account>(RalcRaw<A, Pointer, T>);
+pub struct RalcPtr<T: ?Sized, A: Account, Alloc: Allocator = alloc::Global>(
+    RalcRaw<A, Pointer, T, Alloc>,
+);
+
+unsafe impl<T: ?Sized + Send + Sync, A: Account + Sync, Alloc: Allocator + Send + Sync> Send
+    for RalcPtr<T, A, Alloc>
+{
+}
+unsafe impl<T: ?Sized + Send + Sync, A: Account + Sync, Alloc: Allocator + Send + Sync> Sync
+    for RalcPtr<T, A, Alloc>
+{
+}
+#[cfg(feature = "nightly")]
+impl<T: ?Sized + Unsize<U>, U: ?Sized, A: Account, Alloc: Allocator>
+    CoerceUnsized<RalcPtr<U, A, Alloc>> for RalcPtr<T, A, Alloc>
+where
+    RalcRaw<A, Pointer, T, Alloc>: CoerceUnsized<RalcRaw<A, Pointer, U, Alloc>>,
+{
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> Clone for RalcPtr<T, A, Alloc> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> Copy for RalcPtr<T, A, Alloc> {}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> RalcPtr<T, A, Alloc> {
+    /// Check if this pointer is still valid, i.e. its allocation
+    /// has not been dropped by its owner.
+    pub fn check(&self) -> bool {
+        self.0.is_owned()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn account(&self) -> &A {
+        self.0.account()
+    }
+
+    /// Identify the account tracking this allocation, or which tracked it if stale.
+    pub fn account_id(&self) -> AccountId {
+        AccountId::of(self.0.account())
+    }
+
+    /// The reallocation count of the account while it tracked this allocation as owned,
+    /// which tells it apart from other allocations tracked by the same account.
+    pub fn generation(&self) -> u64 {
+        self.0.generation()
+    }
+
+    /// Check whether a writer panicked while holding access to the data.
+    /// Stale pointers are never poisoned.
+    pub fn is_poisoned(&self) -> bool {
+        self.0.account().is_poisoned() && self.check()
+    }
+
+    /// Get a readable reference through this pointer. Returns immediately if access
+    /// cannot be acquired.
+    pub fn try_read(&self) -> Result<RalcRef<T, A, Alloc>> {
+        self.acquire_ref(|raw| raw.try_acquire_ref().ok_or(NoAccess::Blocked))
+    }
+
+    /// Get a writable reference through this pointer. Returns immediately if access
+    /// cannot be acquired.
+    pub fn try_write(&self) -> Result<RalcMut<T, A, Alloc>> {
+        self.acquire_mut(|raw| raw.try_acquire_mut().ok_or(NoAccess::Blocked))
+    }
+
+    /// Get an upgradable reference through this pointer. Returns immediately if access
+    /// cannot be acquired.
+    pub fn try_upgradable_read(&self) -> Result<RalcUpgradable<T, A, Alloc>> {
+        self.acquire_upgradable(|raw| raw.try_acquire_upgradable().ok_or(NoAccess::Blocked))
+    }
+
+    /// Get an upgradable reference through this pointer. Waits for access if the account
+    /// supports it, and fails with [`NoAccess::Deadlock`] otherwise.
+    ///
+    /// Fails with [`NoAccess::Stale`] if the owner dropped the data while waiting.
+    pub fn upgradable_read(&self) -> Result<RalcUpgradable<T, A, Alloc>> {
+        self.acquire_upgradable(|raw| {
+            deadlock::check(self.account_id(), Held::Upgradable)?;
+            raw.acquire_upgradable().ok_or(NoAccess::Deadlock(None))
+        })
+    }
+
+    /// Get a readable reference through this pointer. Waits for access if the account
+    /// supports it, and fails with [`NoAccess::Deadlock`] otherwise.
+    ///
+    /// Fails with [`NoAccess::Stale`] if the owner dropped the data while waiting.
+    pub fn read(&self) -> Result<RalcRef<T, A, Alloc>> {
+        self.acquire_ref(|raw| {
+            deadlock::check(self.account_id(), Held::Reference)?;
+            let raw = if deadlock::holds_reference(self.account_id()) {
+                raw.acquire_ref_recursive()
+            } else {
+                raw.acquire_ref()
+            };
+            raw.ok_or(NoAccess::Deadlock(None))
+        })
+    }
+
+    /// Get a writable reference through this pointer. Waits for access if the account
+    /// supports it, and fails with [`NoAccess::Deadlock`] otherwise.
+    ///
+    /// Fails with [`NoAccess::Stale`] if the owner dropped the data while waiting.
+    pub fn write(&self) -> Result<RalcMut<T, A, Alloc>> {
+        self.acquire_mut(|raw| {
+            deadlock::check(self.account_id(), Held::Mutation)?;
+            raw.acquire_mut().ok_or(NoAccess::Deadlock(None))
+        })
+    }
+
+    /// Get a readable reference through this pointer, waiting at most `timeout` for access.
+    /// Fails with [`NoAccess::Timeout`] if it runs out, or [`NoAccess::Unsupported`] if the
+    /// account does not support waiting.
+    ///
+    /// Fails with [`NoAccess::Stale`] if the owner dropped the data while waiting.
+    pub fn try_read_for(&self, timeout: Duration) -> Result<RalcRef<T, A, Alloc>> {
+        self.try_read_until(deadline_after(timeout))
+    }
+
+    /// Get a writable reference through this pointer, waiting at most `timeout` for access.
+    /// Fails with [`NoAccess::Timeout`] if it runs out, or [`NoAccess::Unsupported`] if the
+    /// account does not support waiting.
+    ///
+    /// Fails with [`NoAccess::Stale`] if the owner dropped the data while waiting.
+    pub fn try_write_for(&self, timeout: Duration) -> Result<RalcMut<T, A, Alloc>> {
+        self.try_write_until(deadline_after(timeout))
+    }
+
+    /// Get a readable reference through this pointer, waiting until `deadline` for access.
+    /// Fails with [`NoAccess::Timeout`] if it passes, or [`NoAccess::Unsupported`] if the
+    /// account does not support waiting.
+    ///
+    /// Fails with [`NoAccess::Stale`] if the owner dropped the data while waiting.
+    pub fn try_read_until(&self, deadline: Instant) -> Result<RalcRef<T, A, Alloc>> {
+        self.acquire_ref(|raw| Ok(raw.acquire_ref_until(deadline)?))
+    }
+
+    /// Get a writable reference through this pointer, waiting until `deadline` for access.
+    /// Fails with [`NoAccess::Timeout`] if it passes, or [`NoAccess::Unsupported`] if the
+    /// account does not support waiting.
+    ///
+    /// Fails with [`NoAccess::Stale`] if the owner dropped the data while waiting.
+    pub fn try_write_until(&self, deadline: Instant) -> Result<RalcMut<T, A, Alloc>> {
+        self.acquire_mut(|raw| Ok(raw.acquire_mut_until(deadline)?))
+    }
+
+    fn acquire_ref(
+        &self,
+        acquire: impl FnOnce(
+            RalcRaw<A, Pointer, T, Alloc>,
+        ) -> Result<RalcRaw<A, Pointer, T, Alloc>, RalcRef<T, A, Alloc>>,
+    ) -> Result<RalcRef<T, A, Alloc>> {
+        if !self.check() {
+            return Err(NoAccess::Stale);
+        }
+
+        let raw = acquire(self.0)?;
+
+        if !raw.is_owned() {
+            unsafe {
+                // SAFETY:
+                // 1. `raw` is not used again
+                // 2. Acquired just above
+                raw.drop_stale_ref();
+            }
+            return Err(NoAccess::Stale);
+        }
+
+        unpoisoned(self.0.account(), RalcRef::new(raw.switch_marker()))
+    }
+
+    fn acquire_mut(
+        &self,
+        acquire: impl FnOnce(
+            RalcRaw<A, Pointer, T, Alloc>,
+        ) -> Result<RalcRaw<A, Pointer, T, Alloc>, RalcMut<T, A, Alloc>>,
+    ) -> Result<RalcMut<T, A, Alloc>> {
+        if !self.check() {
+            return Err(NoAccess::Stale);
+        }
+
+        let raw = acquire(self.0)?;
+
+        if !raw.is_owned() {
+            unsafe {
+                // SAFETY:
+                // 1. `raw` is not used again
+                // 2. Acquired just above
+                raw.drop_stale_mut();
+            }
+            return Err(NoAccess::Stale);
+        }
+
+        unpoisoned(self.0.account(), RalcMut::new(raw.switch_marker()))
+    }
+
+    fn acquire_upgradable(
+        &self,
+        acquire: impl FnOnce(
+            RalcRaw<A, Pointer, T, Alloc>,
+        )
+            -> Result<RalcRaw<A, Pointer, T, Alloc>, RalcUpgradable<T, A, Alloc>>,
+    ) -> Result<RalcUpgradable<T, A, Alloc>> {
+        if !self.check() {
+            return Err(NoAccess::Stale);
+        }
+
+        let raw = acquire(self.0)?;
+
+        if !raw.is_owned() {
+            unsafe {
+                // SAFETY:
+                // 1. `raw` is not used again
+                // 2. Acquired just above
+                raw.drop_stale_upgradable();
+            }
+            return Err(NoAccess::Stale);
+        }
+
+        unpoisoned(self.0.account(), RalcUpgradable::new(raw.switch_marker()))
+    }
+}
+
+impl<T, A: Account, Alloc: Allocator> RalcPtr<T, A, Alloc> {
+    /// Split this pointer into its account, its generation and its data pointer, such as
+    /// to keep them in atomics. The data pointer may only be dereferenced through a
+    /// pointer rebuilt with [`RalcPtr::from_raw_parts`].
+    pub fn into_raw_parts(self) -> (NonNull<A>, u64, NonNull<T>) {
+        let data = unsafe {
+            // SAFETY:
+            // 1. `T` is sized
+            // 2. Points to the payload of this allocation, or of a later one of the account
+            Payload::data_ptr(self.0.payload())
+        };
+        (
+            NonNull::from_ref(self.0.account()),
+            self.0.generation(),
+            data,
+        )
+    }
+
+    /// Rebuild a pointer from [`RalcPtr::into_raw_parts`]. Fails if the generation is
+    /// ahead of the account, which no pointer to it could have had.
+    ///
+    /// # Safety
+    /// 1. The parts must come from [`RalcPtr::into_raw_parts`] on a pointer of this type,
+    ///    which could still be used where this is called
+    pub unsafe fn from_raw_parts(
+        account: NonNull<A>,
+        generation: u64,
+        data: NonNull<T>,
+    ) -> Option<Self> {
+        let account = unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller, as the original pointer held this account
+            AccPtr::new(account.as_ref())
+        };
+        if generation >= 1 << 56 || generation > account.check() {
+            return None;
+        }
+
+        Some(Self(unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller, as the original pointer was made for this
+            //    allocation while owned, and its data pointer split off the payload
+            RalcRaw::from_generation(account, generation, Payload::from_data_ptr(data))
+        }))
+    }
+}
+
+impl<T: fmt::Debug + ?Sized, A: Account, Alloc: Allocator> fmt::Debug for RalcPtr<T, A, Alloc> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let data = self.try_read();
+        let name = std::any::type_name::<T>();
+        f.debug_tuple("RalcPtr")
+            .field(match &data.as_deref() {
+                Ok(data) => data,
+                Err(_) => &name,
+            })
+            .finish()
+    }
+}
+
+impl<T: fmt::Display + ?Sized, A: Account, Alloc: Allocator> fmt::Display for RalcPtr<T, A, Alloc> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let marker = if f.alternate() { " (borrowed)" } else { "" };
+        if let Ok(r) = self.try_read() {
+            write!(f, "{}{}", r, marker)
+        } else {
+            write!(f, "<unavailable>{}", marker)
+        }
+    }
+}
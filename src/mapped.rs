@@ -0,0 +1,626 @@
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use ralc_internals::{
+    accounts::Account,
+    alloc::{self, Allocator},
+};
+
+use crate::{AccountId, RalcMut, RalcPtr, RalcRef, Result};
+
+/// A part of `T`, such as a field, which a [`RalcPtr`] can be projected to.
+///
+/// Fields of structs passed to [`fields!`](crate::fields) are available as associated
+/// constants of the same name.
+pub struct Field<T: ?Sized, U: ?Sized> {
+    get: fn(&T) -> &U,
+    get_mut: fn(&mut T) -> &mut U,
+}
+
+impl<T: ?Sized, U: ?Sized> Field<T, U> {
+    /// Both functions must find the same part of `T`.
+    pub const fn new(get: fn(&T) -> &U, get_mut: fn(&mut T) -> &mut U) -> Self {
+        Self { get, get_mut }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> Clone for Field<T, U> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> Copy for Field<T, U> {}
+
+/// Declare a struct with named fields, along with a [`Field`] constant for each of them,
+/// so that pointers to it can be projected with [`RalcPtr::project`].
+///
+/// ```
+/// racl::fields! {
+///     #[derive(Debug)]
+///     pub struct State {
+///         pub count: u32,
+///         name: String,
+///     }
+/// }
+///
+/// let owned = racl::RalcBox::<_, racl::ThreadLocal>::new(State {
+///     count: 0,
+///     name: String::new(),
+/// });
+/// let count = owned.borrow().project(State::count);
+/// *count.try_write().unwrap() += 1;
+/// assert_eq!(owned.try_read().unwrap().count, 1);
+/// ```
+///
+/// Structs declared this way cannot have generics or a where clause. For those, and
+/// for structs declared elsewhere, write an `impl` header instead and list the fields
+/// to make constants for, with their types. Fields of tuple structs are named with
+/// `name = index`.
+///
+/// ```
+/// #[derive(Debug)]
+/// pub struct Labelled<T: Clone>(pub T, String);
+///
+/// racl::fields! {
+///     impl<T: Clone> Labelled<T> {
+///         pub value = 0: T,
+///         label = 1: String,
+///     }
+/// }
+///
+/// let owned = racl::RalcBox::<_, racl::ThreadLocal>::new(Labelled(1, String::new()));
+/// let value = owned.borrow().project(Labelled::value);
+/// *value.try_write().unwrap() += 1;
+/// assert_eq!(owned.try_read().unwrap().0, 2);
+/// ```
+#[macro_export]
+macro_rules! fields {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_attr:meta])* $field_vis:vis $field:ident : $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $($(#[$field_attr])* $field_vis $field: $ty),*
+        }
+
+        $crate::fields! {
+            impl $name {
+                $($field_vis $field: $ty),*
+            }
+        }
+    };
+    (impl $($rest:tt)*) => {
+        $crate::fields!(@header [] $($rest)*);
+    };
+    // Gather the header of the impl, up to the braces listing the fields.
+    (@header [$($header:tt)*] {
+        $($field_vis:vis $field:ident $(= $member:tt)? : $ty:ty),* $(,)?
+    }) => {
+        #[allow(non_upper_case_globals)]
+        impl $($header)* {
+            $(
+                $field_vis const $field: $crate::Field<Self, $ty> = $crate::Field::new(
+                    |data| &$crate::fields!(@member data $field $($member)?),
+                    |data| &mut $crate::fields!(@member data $field $($member)?),
+                );
+            )*
+        }
+    };
+    (@header [$($header:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::fields!(@header [$($header)* $next] $($rest)*);
+    };
+    (@member $data:ident $field:ident) => {
+        $data.$field
+    };
+    (@member $data:ident $field:ident $member:tt) => {
+        $data.$member
+    };
+}
+
+/// Shared access to part of the data of a ralc, as made by [`RalcRef::map`].
+pub struct MappedRalcRef<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator = alloc::Global> {
+    guard: RalcRef<T, A, Alloc>,
+    /// # Safety invariants:
+    /// 1. Points into the data of `guard`
+    part: NonNull<U>,
+}
+
+unsafe impl<
+    T: ?Sized + Send + Sync,
+    U: ?Sized + Send + Sync,
+    A: Account + Sync,
+    Alloc: Allocator + Send + Sync,
+> Send for MappedRalcRef<T, U, A, Alloc>
+{
+}
+unsafe impl<
+    T: ?Sized + Send + Sync,
+    U: ?Sized + Send + Sync,
+    A: Account + Sync,
+    Alloc: Allocator + Send + Sync,
+> Sync for MappedRalcRef<T, U, A, Alloc>
+{
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> RalcRef<T, A, Alloc> {
+    /// Narrow access down to a part of the data, such as a field.
+    pub fn map<U: ?Sized>(self, f: impl FnOnce(&T) -> &U) -> MappedRalcRef<T, U, A, Alloc> {
+        let part = NonNull::from(f(&self));
+        MappedRalcRef { guard: self, part }
+    }
+
+    /// Narrow access down to a part of the data which may not exist, such as an element.
+    pub fn try_map<U: ?Sized>(
+        self,
+        f: impl FnOnce(&T) -> Option<&U>,
+    ) -> std::result::Result<MappedRalcRef<T, U, A, Alloc>, Self> {
+        match f(&self).map(NonNull::from) {
+            Some(part) => Ok(MappedRalcRef { guard: self, part }),
+            None => Err(self),
+        }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator> MappedRalcRef<T, U, A, Alloc> {
+    /// Narrow access down further.
+    pub fn map<V: ?Sized>(self, f: impl FnOnce(&U) -> &V) -> MappedRalcRef<T, V, A, Alloc> {
+        let part = NonNull::from(f(&self));
+        MappedRalcRef {
+            guard: self.guard,
+            part,
+        }
+    }
+
+    /// Narrow access down further, to a part which may not exist.
+    pub fn try_map<V: ?Sized>(
+        self,
+        f: impl FnOnce(&U) -> Option<&V>,
+    ) -> std::result::Result<MappedRalcRef<T, V, A, Alloc>, Self> {
+        match f(&self).map(NonNull::from) {
+            Some(part) => Ok(MappedRalcRef {
+                guard: self.guard,
+                part,
+            }),
+            None => Err(self),
+        }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator> Clone for MappedRalcRef<T, U, A, Alloc> {
+    fn clone(&self) -> Self {
+        Self {
+            guard: self.guard.clone(),
+            part: self.part,
+        }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator> Deref for MappedRalcRef<T, U, A, Alloc> {
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            // SAFETY:
+            // 1. A reference permit is held by `guard`
+            self.part.as_ref()
+        }
+    }
+}
+
+impl<T: ?Sized, U: fmt::Debug + ?Sized, A: Account, Alloc: Allocator> fmt::Debug
+    for MappedRalcRef<T, U, A, Alloc>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.deref(), f)
+    }
+}
+
+impl<T: ?Sized, U: fmt::Display + ?Sized, A: Account, Alloc: Allocator> fmt::Display
+    for MappedRalcRef<T, U, A, Alloc>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.deref(), f)
+    }
+}
+
+/// Exclusive access to part of the data of a ralc, as made by [`RalcMut::map`].
+pub struct MappedRalcMut<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator = alloc::Global> {
+    guard: RalcMut<T, A, Alloc>,
+    /// # Safety invariants:
+    /// 1. Points into the data of `guard`, which is not accessed through it
+    part: NonNull<U>,
+}
+
+unsafe impl<
+    T: ?Sized + Send + Sync,
+    U: ?Sized + Send + Sync,
+    A: Account + Sync,
+    Alloc: Allocator + Send + Sync,
+> Send for MappedRalcMut<T, U, A, Alloc>
+{
+}
+unsafe impl<
+    T: ?Sized + Send + Sync,
+    U: ?Sized + Send + Sync,
+    A: Account + Sync,
+    Alloc: Allocator + Send + Sync,
+> Sync for MappedRalcMut<T, U, A, Alloc>
+{
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> RalcMut<T, A, Alloc> {
+    /// Narrow access down to a part of the data, such as a field.
+    pub fn map<U: ?Sized>(
+        mut self,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> MappedRalcMut<T, U, A, Alloc> {
+        let part = NonNull::from(f(&mut self));
+        MappedRalcMut { guard: self, part }
+    }
+
+    /// Narrow access down to a part of the data which may not exist, such as an element.
+    pub fn try_map<U: ?Sized>(
+        mut self,
+        f: impl FnOnce(&mut T) -> Option<&mut U>,
+    ) -> std::result::Result<MappedRalcMut<T, U, A, Alloc>, Self> {
+        match f(&mut self).map(NonNull::from) {
+            Some(part) => Ok(MappedRalcMut { guard: self, part }),
+            None => Err(self),
+        }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator> MappedRalcMut<T, U, A, Alloc> {
+    /// Narrow access down further.
+    pub fn map<V: ?Sized>(
+        mut self,
+        f: impl FnOnce(&mut U) -> &mut V,
+    ) -> MappedRalcMut<T, V, A, Alloc> {
+        let part = NonNull::from(f(&mut self));
+        MappedRalcMut {
+            guard: self.guard,
+            part,
+        }
+    }
+
+    /// Narrow access down further, to a part which may not exist.
+    pub fn try_map<V: ?Sized>(
+        mut self,
+        f: impl FnOnce(&mut U) -> Option<&mut V>,
+    ) -> std::result::Result<MappedRalcMut<T, V, A, Alloc>, Self> {
+        match f(&mut self).map(NonNull::from) {
+            Some(part) => Ok(MappedRalcMut {
+                guard: self.guard,
+                part,
+            }),
+            None => Err(self),
+        }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator> Deref for MappedRalcMut<T, U, A, Alloc> {
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            // SAFETY:
+            // 1. The mutation permit is held by `guard`
+            self.part.as_ref()
+        }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator> DerefMut
+    for MappedRalcMut<T, U, A, Alloc>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe {
+            // SAFETY:
+            // 1. The mutation permit is held by `guard`
+            self.part.as_mut()
+        }
+    }
+}
+
+impl<T: ?Sized, U: fmt::Debug + ?Sized, A: Account, Alloc: Allocator> fmt::Debug
+    for MappedRalcMut<T, U, A, Alloc>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.deref(), f)
+    }
+}
+
+impl<T: ?Sized, U: fmt::Display + ?Sized, A: Account, Alloc: Allocator> fmt::Display
+    for MappedRalcMut<T, U, A, Alloc>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.deref(), f)
+    }
+}
+
+/// Exclusive access to one of several disjoint parts of the data of a ralc, as made by
+/// [`RalcMut::map_split`].
+///
+/// The parts share the mutation permit, which is only relinquished once the last of them
+/// is dropped, on whichever thread that happens.
+pub struct SplitRalcMut<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator = alloc::Global> {
+    guard: Arc<RalcMut<T, A, Alloc>>,
+    /// # Safety invariants:
+    /// 1. Points into the data of `guard`, disjoint from the parts of other guards sharing
+    ///    it, and none of them access the data through it
+    part: NonNull<U>,
+}
+
+unsafe impl<
+    T: ?Sized + Send + Sync,
+    U: ?Sized + Send + Sync,
+    A: Account + Sync,
+    Alloc: Allocator + Send + Sync,
+> Send for SplitRalcMut<T, U, A, Alloc>
+{
+}
+unsafe impl<
+    T: ?Sized + Send + Sync,
+    U: ?Sized + Send + Sync,
+    A: Account + Sync,
+    Alloc: Allocator + Send + Sync,
+> Sync for SplitRalcMut<T, U, A, Alloc>
+{
+}
+
+/// The two parts made by [`RalcMut::map_split`].
+type SplitPair<T, U, V, A, Alloc> = (SplitRalcMut<T, U, A, Alloc>, SplitRalcMut<T, V, A, Alloc>);
+
+impl<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator> Drop for SplitRalcMut<T, U, A, Alloc> {
+    fn drop(&mut self) {
+        // The shared guard only notices a panic if this part was the last.
+        if std::thread::panicking() {
+            self.guard.0.account().poison();
+        }
+    }
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> RalcMut<T, A, Alloc> {
+    /// Split exclusive access into two disjoint parts of the data, such as two fields,
+    /// which can be handed out separately.
+    pub fn map_split<U: ?Sized, V: ?Sized>(
+        mut self,
+        f: impl FnOnce(&mut T) -> (&mut U, &mut V),
+    ) -> SplitPair<T, U, V, A, Alloc> {
+        let (left, right) = f(&mut self);
+        let (left, right) = (NonNull::from(left), NonNull::from(right));
+        SplitRalcMut::pair(Arc::new(self), left, right)
+    }
+
+    /// Split exclusive access into any number of disjoint parts of the data, such as
+    /// chunks of a slice, which can be handed out separately.
+    pub fn split_parts<U: ?Sized>(
+        mut self,
+        f: impl FnOnce(&mut T) -> Vec<&mut U>,
+    ) -> Vec<SplitRalcMut<T, U, A, Alloc>> {
+        let parts = f(&mut self).into_iter().map(NonNull::from).collect();
+        SplitRalcMut::all(Arc::new(self), parts)
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator> SplitRalcMut<T, U, A, Alloc> {
+    /// Narrow access down further.
+    pub fn map<V: ?Sized>(
+        mut self,
+        f: impl FnOnce(&mut U) -> &mut V,
+    ) -> SplitRalcMut<T, V, A, Alloc> {
+        let part = NonNull::from(f(&mut self));
+        SplitRalcMut {
+            guard: self.guard.clone(),
+            part,
+        }
+    }
+
+    /// Split this part further into two disjoint parts.
+    pub fn map_split<V: ?Sized, W: ?Sized>(
+        mut self,
+        f: impl FnOnce(&mut U) -> (&mut V, &mut W),
+    ) -> SplitPair<T, V, W, A, Alloc> {
+        let (left, right) = f(&mut self);
+        let (left, right) = (NonNull::from(left), NonNull::from(right));
+        SplitRalcMut::pair(self.guard.clone(), left, right)
+    }
+
+    /// Split this part further into any number of disjoint parts.
+    pub fn split_parts<V: ?Sized>(
+        mut self,
+        f: impl FnOnce(&mut U) -> Vec<&mut V>,
+    ) -> Vec<SplitRalcMut<T, V, A, Alloc>> {
+        let parts = f(&mut self).into_iter().map(NonNull::from).collect();
+        SplitRalcMut::all(self.guard.clone(), parts)
+    }
+
+    /// Number of parts sharing the mutation permit with this one, including itself.
+    pub fn parts(&self) -> usize {
+        Arc::strong_count(&self.guard)
+    }
+
+    fn pair<V: ?Sized>(
+        guard: Arc<RalcMut<T, A, Alloc>>,
+        left: NonNull<U>,
+        right: NonNull<V>,
+    ) -> SplitPair<T, U, V, A, Alloc> {
+        let right = SplitRalcMut {
+            guard: guard.clone(),
+            part: right,
+        };
+        (SplitRalcMut { guard, part: left }, right)
+    }
+
+    fn all(guard: Arc<RalcMut<T, A, Alloc>>, parts: Vec<NonNull<U>>) -> Vec<Self> {
+        parts
+            .into_iter()
+            .map(|part| Self {
+                guard: guard.clone(),
+                part,
+            })
+            .collect()
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator> Deref for SplitRalcMut<T, U, A, Alloc> {
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            // SAFETY:
+            // 1. The shared mutation permit is held by `guard`, and the part is disjoint
+            self.part.as_ref()
+        }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator> DerefMut for SplitRalcMut<T, U, A, Alloc> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe {
+            // SAFETY:
+            // 1. The shared mutation permit is held by `guard`, and the part is disjoint
+            self.part.as_mut()
+        }
+    }
+}
+
+impl<T: ?Sized, U: fmt::Debug + ?Sized, A: Account, Alloc: Allocator> fmt::Debug
+    for SplitRalcMut<T, U, A, Alloc>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.deref(), f)
+    }
+}
+
+impl<T: ?Sized, U: fmt::Display + ?Sized, A: Account, Alloc: Allocator> fmt::Display
+    for SplitRalcMut<T, U, A, Alloc>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.deref(), f)
+    }
+}
+
+/// A weak pointer to a [`Field`] of the data of a ralc, as made by [`RalcPtr::project`].
+///
+/// It goes stale together with the ralc, and accessing it takes the permits of the
+/// whole ralc.
+pub struct MappedRalcPtr<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator = alloc::Global> {
+    ptr: RalcPtr<T, A, Alloc>,
+    field: Field<T, U>,
+}
+
+impl<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator> Clone for MappedRalcPtr<T, U, A, Alloc> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator> Copy for MappedRalcPtr<T, U, A, Alloc> {}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> RalcPtr<T, A, Alloc> {
+    /// Point at a field of the data.
+    pub fn project<U: ?Sized>(&self, field: Field<T, U>) -> MappedRalcPtr<T, U, A, Alloc> {
+        MappedRalcPtr { ptr: *self, field }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, A: Account, Alloc: Allocator> MappedRalcPtr<T, U, A, Alloc> {
+    /// The pointer to the whole data.
+    pub fn parent(&self) -> RalcPtr<T, A, Alloc> {
+        self.ptr
+    }
+
+    /// Check if this pointer is still valid, i.e. its allocation
+    /// has not been dropped by its owner.
+    pub fn check(&self) -> bool {
+        self.ptr.check()
+    }
+
+    /// Identify the account tracking this allocation, or which tracked it if stale.
+    pub fn account_id(&self) -> AccountId {
+        self.ptr.account_id()
+    }
+
+    /// Check whether a writer panicked while holding access to the data.
+    /// Stale pointers are never poisoned.
+    pub fn is_poisoned(&self) -> bool {
+        self.ptr.is_poisoned()
+    }
+
+    /// Get a readable reference to the field. Returns immediately if access
+    /// cannot be acquired.
+    pub fn try_read(&self) -> Result<MappedRalcRef<T, U, A, Alloc>> {
+        self.map_ref(self.ptr.try_read())
+    }
+
+    /// Get a writable reference to the field. Returns immediately if access
+    /// cannot be acquired.
+    pub fn try_write(&self) -> Result<MappedRalcMut<T, U, A, Alloc>> {
+        self.map_mut(self.ptr.try_write())
+    }
+
+    /// Get a readable reference to the field, waiting as [`RalcPtr::read`] does.
+    pub fn read(&self) -> Result<MappedRalcRef<T, U, A, Alloc>> {
+        self.map_ref(self.ptr.read())
+    }
+
+    /// Get a writable reference to the field, waiting as [`RalcPtr::write`] does.
+    pub fn write(&self) -> Result<MappedRalcMut<T, U, A, Alloc>> {
+        self.map_mut(self.ptr.write())
+    }
+
+    /// Get a readable reference to the field, waiting at most `timeout` for access.
+    pub fn try_read_for(&self, timeout: Duration) -> Result<MappedRalcRef<T, U, A, Alloc>> {
+        self.map_ref(self.ptr.try_read_for(timeout))
+    }
+
+    /// Get a writable reference to the field, waiting at most `timeout` for access.
+    pub fn try_write_for(&self, timeout: Duration) -> Result<MappedRalcMut<T, U, A, Alloc>> {
+        self.map_mut(self.ptr.try_write_for(timeout))
+    }
+
+    /// Get a readable reference to the field, waiting until `deadline` for access.
+    pub fn try_read_until(&self, deadline: Instant) -> Result<MappedRalcRef<T, U, A, Alloc>> {
+        self.map_ref(self.ptr.try_read_until(deadline))
+    }
+
+    /// Get a writable reference to the field, waiting until `deadline` for access.
+    pub fn try_write_until(&self, deadline: Instant) -> Result<MappedRalcMut<T, U, A, Alloc>> {
+        self.map_mut(self.ptr.try_write_until(deadline))
+    }
+
+    fn map_ref(&self, res: Result<RalcRef<T, A, Alloc>>) -> Result<MappedRalcRef<T, U, A, Alloc>> {
+        let map = |guard: RalcRef<T, A, Alloc>| guard.map(self.field.get);
+        res.map(map).map_err(|e| e.map_poisoned(map))
+    }
+
+    fn map_mut(&self, res: Result<RalcMut<T, A, Alloc>>) -> Result<MappedRalcMut<T, U, A, Alloc>> {
+        let map = |guard: RalcMut<T, A, Alloc>| guard.map(self.field.get_mut);
+        res.map(map).map_err(|e| e.map_poisoned(map))
+    }
+}
+
+impl<T: ?Sized, U: fmt::Debug + ?Sized, A: Account, Alloc: Allocator> fmt::Debug
+    for MappedRalcPtr<T, U, A, Alloc>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let data = self.try_read();
+        let name = std::any::type_name::<U>();
+        f.debug_tuple("MappedRalcPtr")
+            .field(match &data.as_deref() {
+                Ok(data) => data,
+                Err(_) => &name,
+            })
+            .finish()
+    }
+}
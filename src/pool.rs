@@ -0,0 +1,263 @@
+use std::{
+    cell::{Cell, UnsafeCell},
+    marker::PhantomData,
+    ptr::NonNull,
+    sync::atomic::{AtomicU32, AtomicU64},
+};
+
+use ralc_internals::{
+    accounts::{
+        AccPtr, Account,
+        balances::Balance,
+        freeable::Freeable,
+        permits::Permits,
+        slots::{AtomicErasedPtr, DataSlot, ErasedPtr, InAccount},
+    },
+    alloc::Allocator,
+    delegate_account_impl,
+    delegate_impl::DelegateAccountImpl,
+};
+
+use crate::{RalcBox, ledgers::NewAccount};
+
+#[cfg(not(feature = "bumpalo"))]
+type AccountBook<N> = crate::ledgers::RetainingBook<N>;
+#[cfg(feature = "bumpalo")]
+type AccountBook<N> = crate::ledgers::BumpyBook<N>;
+
+/// A pool whose ralcs take no synchronized locks and are never `Send`.
+pub type LocalPool = PoolAllocator<Cell<u64>, Cell<u32>>;
+
+/// A pool whose ralcs are shareable across threads for as long as the pool lives.
+pub type SyncPool = PoolAllocator<AtomicU64, AtomicU32>;
+
+/// An allocator of accounts which are all deallocated together with it.
+///
+/// Ralcs allocated from a pool borrow it, so no [`RalcBox`], [`RalcPtr`](crate::RalcPtr),
+/// [`RalcRef`](crate::RalcRef), or [`RalcMut`](crate::RalcMut) can outlive it.
+/// If any ralc was forgotten instead of dropped, the pool's accounts are leaked
+/// rather than deallocated.
+///
+/// ```compile_fail
+/// let pool = racl::LocalPool::new();
+/// let ptr = pool.ralc(0).borrow();
+/// std::mem::drop(pool);
+/// ptr.check();
+/// ```
+pub struct PoolAllocator<B: Balance + Default, P: Permits + Default>(Box<Pool<B, P>>);
+
+struct Pool<B: Balance + Default, P: Permits + Default> {
+    lock: P,
+    book: UnsafeCell<AccountBook<PoolAccounts<B, P>>>,
+}
+
+/// Creates accounts pointing back at their pool.
+struct PoolAccounts<B: Balance + Default, P: Permits + Default>(NonNull<Pool<B, P>>);
+
+impl<B: Balance + Default, P: Permits + Default> NewAccount for PoolAccounts<B, P> {
+    type Account = PoolAccount<'static, B, P>;
+
+    fn new_account(&self) -> Self::Account {
+        PoolAccount {
+            balance: B::default(),
+            permits: P::default(),
+            pool: self.0,
+            data: AtomicErasedPtr::new(),
+            _lifetime: PhantomData,
+        }
+    }
+}
+
+unsafe impl<B: Balance + Default + Send, P: Permits + Default + Send> Send for PoolAllocator<B, P> {}
+unsafe impl<B: Balance + Default + Sync, P: Permits + Default + Sync> Sync for PoolAllocator<B, P> {}
+
+impl<B: Balance + Default, P: Permits + Default> Default for PoolAllocator<B, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: Balance + Default, P: Permits + Default> PoolAllocator<B, P> {
+    pub fn new() -> Self {
+        let mut pool = Box::<Pool<B, P>>::new_uninit();
+        let ptr = NonNull::from_mut(&mut *pool).cast();
+        Self(Box::write(
+            pool,
+            Pool {
+                lock: P::default(),
+                book: UnsafeCell::new(AccountBook::new(PoolAccounts(ptr))),
+            },
+        ))
+    }
+
+    /// Allocate a ralc whose lifetime is bound to this pool.
+    pub fn ralc<T>(&self, value: T) -> RalcBox<T, PoolAccount<'_, B, P>> {
+        self.ralc_box(Box::new(value))
+    }
+
+    /// Allocate a ralc from an existing box, whose lifetime is bound to this pool.
+    pub fn ralc_box<T: ?Sized>(&self, data: Box<T>) -> RalcBox<T, PoolAccount<'_, B, P>> {
+        unsafe {
+            // SAFETY:
+            // 1. Freshly allocated
+            // 2. Freshly allocated or returned to the free list non-exhausted,
+            //    with all permits released
+            RalcBox::from_parts(self.allocate(), data)
+        }
+    }
+
+    /// Allocate a ralc whose lifetime is bound to this pool, with its data allocated
+    /// by `alloc`.
+    pub fn ralc_in<T, Alloc: Allocator>(
+        &self,
+        value: T,
+        alloc: Alloc,
+    ) -> RalcBox<T, PoolAccount<'_, B, P>, Alloc> {
+        unsafe {
+            // SAFETY:
+            // 1. Freshly allocated
+            // 2. Freshly allocated or returned to the free list non-exhausted,
+            //    with all permits released
+            RalcBox::from_parts_in(self.allocate(), value, alloc)
+        }
+    }
+
+    /// Set the number of accounts allocated in the next chunk, and
+    /// the limit to which subsequent chunk sizes may grow.
+    pub fn set_chunks(&self, chunk: usize, limit: usize) {
+        self.0.with_book(|book| book.set_chunks(chunk, limit))
+    }
+
+    /// Number of accounts in this pool currently tracking an allocation.
+    pub fn outstanding(&self) -> usize {
+        self.0.with_book(|book| book.outstanding())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn total_allocations(&self) -> usize {
+        self.0.with_book(|book| book.total_allocations())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn expansions(&self) -> usize {
+        self.0.with_book(|book| book.expansions())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn free_count(&self) -> usize {
+        self.0.with_book(|book| book.free_count())
+    }
+
+    fn allocate(&self) -> AccPtr<PoolAccount<'_, B, P>> {
+        let account = self.0.with_book(|book| book.allocate());
+        unsafe {
+            // SAFETY:
+            // 1.3 The chunks of the book live as long as the pool,
+            //     which `PoolAccount<'_, B, P>` borrows
+            AccPtr::new(&*account)
+        }
+    }
+}
+
+impl<B: Balance + Default, P: Permits + Default> Pool<B, P> {
+    fn with_book<X>(&self, f: impl FnOnce(&mut AccountBook<PoolAccounts<B, P>>) -> X) -> X {
+        while !self.lock.try_mutation() {
+            std::hint::spin_loop();
+        }
+
+        let res = f(unsafe {
+            // SAFETY:
+            // 1. The mutation permit on `lock` grants exclusive access,
+            //    and `f` never reenters the pool
+            &mut *self.book.get()
+        });
+
+        unsafe {
+            // SAFETY:
+            // 1. Acquired above
+            self.lock.abandon_mutation();
+        }
+        res
+    }
+}
+
+/// Account type for ralcs allocated from a [`PoolAllocator`], borrowing it for `'a`.
+pub struct PoolAccount<'a, B: Balance + Default, P: Permits + Default> {
+    balance: B,
+    permits: P,
+    pool: NonNull<Pool<B, P>>,
+    data: AtomicErasedPtr,
+    _lifetime: PhantomData<&'a ()>,
+}
+
+unsafe impl<B: Balance + Default + Sync, P: Permits + Default + Sync> Sync
+    for PoolAccount<'_, B, P>
+{
+}
+
+impl<B: Balance + Default, P: Permits + Default> DelegateAccountImpl for PoolAccount<'_, B, P> {
+    type DelegatedBalance = B;
+
+    type DelegatedPermits = P;
+
+    fn balance(&self) -> &Self::DelegatedBalance {
+        &self.balance
+    }
+
+    fn permits(&self) -> &Self::DelegatedPermits {
+        &self.permits
+    }
+}
+
+delegate_account_impl!(['a, B: Balance + Default, P: Permits + Default] PoolAccount<'a, B, P>);
+
+unsafe impl<B: Balance + Default, P: Permits + Default> Freeable for PoolAccount<'_, B, P> {
+    unsafe fn free(&self) {
+        unsafe {
+            // SAFETY:
+            // Guaranteed by caller.
+            self.abandon_mutation();
+        }
+
+        let pool = unsafe {
+            // SAFETY:
+            // 1. The account borrows the pool, which is boxed and so never moves
+            self.pool.as_ref()
+        };
+        pool.with_book(|book| unsafe {
+            // SAFETY:
+            // 1. Allocated by this pool's book, and only the lifetime differs
+            // 2. Guaranteed by caller
+            // 3. Guaranteed by caller
+            book.deallocate(AccPtr::new(&*(self as *const Self).cast::<PoolAccount<
+                'static,
+                B,
+                P,
+            >>()))
+        });
+        // IMPL SAFETY:
+        // 1. See above
+    }
+}
+
+impl<B: Balance + Default, P: Permits + Default> Account for PoolAccount<'_, B, P> {
+    type Data = InAccount;
+}
+
+// SAFETY:
+// 1. Delegated implementation
+unsafe impl<B: Balance + Default, P: Permits + Default> DataSlot for PoolAccount<'_, B, P> {
+    #[inline]
+    unsafe fn set_data(&self, data: ErasedPtr) {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.data.set_data(data)
+        }
+    }
+
+    #[inline]
+    fn data(&self) -> ErasedPtr {
+        self.data.data()
+    }
+}
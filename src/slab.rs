@@ -0,0 +1,400 @@
+use std::{
+    cell::{Cell, UnsafeCell},
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ptr::NonNull,
+    sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering, fence},
+};
+
+use ralc_internals::{
+    RalcRaw,
+    accounts::{
+        AccPtr, Account,
+        balances::Balance,
+        freeable::Freeable,
+        permits::Permits,
+        slots::{DataSlot, ErasedPtr, InAccount},
+    },
+    alloc::{InPlace, Payload},
+    delegate_account_impl,
+    delegate_impl::DelegateAccountImpl,
+};
+
+use crate::{
+    RalcBox, RalcPtr,
+    index::{IndexTable, RalcIndex},
+    ledgers::NewAccount,
+};
+
+#[cfg(not(feature = "bumpalo"))]
+type AccountBook<N> = crate::ledgers::RetainingBook<N>;
+#[cfg(feature = "bumpalo")]
+type AccountBook<N> = crate::ledgers::BumpyBook<N>;
+
+/// A slab whose ralcs take no synchronized locks and are never `Send`.
+pub type LocalSlab<T> = SlabAllocator<T, Cell<u64>, Cell<u32>>;
+
+/// A slab whose ralcs are shareable across threads for as long as the slab lives.
+pub type SyncSlab<T> = SlabAllocator<T, AtomicU64, AtomicU32>;
+
+/// A [`PoolAllocator`](crate::PoolAllocator) for a single type, which stores the data
+/// of each ralc inline next to its account, so that no allocation is made per ralc.
+///
+/// The data is dropped as usual once the last pointer to it is gone, and its slot is
+/// reused together with its account. Ralcs can also be referred to by 8-byte
+/// [`RalcIndex`] handles, which the slab resolves through a table of its accounts.
+///
+/// ```compile_fail
+/// let slab = racl::LocalSlab::new();
+/// let ptr = slab.ralc(0).borrow();
+/// std::mem::drop(slab);
+/// ptr.check();
+/// ```
+pub struct SlabAllocator<T, B: Balance + Default, P: Permits + Default>(Box<Slab<T, B, P>>);
+
+struct Slab<T, B: Balance + Default, P: Permits + Default> {
+    lock: P,
+    book: UnsafeCell<AccountBook<SlabAccounts<T, B, P>>>,
+    /// Accounts by index, inserted under `lock` as they are allocated.
+    table: IndexTable<SlabAccount<'static, T, B, P>>,
+}
+
+/// Creates accounts pointing back at their slab, numbered in order.
+struct SlabAccounts<T, B: Balance + Default, P: Permits + Default> {
+    slab: NonNull<Slab<T, B, P>>,
+    next: Cell<u32>,
+}
+
+impl<T, B: Balance + Default, P: Permits + Default> NewAccount for SlabAccounts<T, B, P> {
+    type Account = SlabAccount<'static, T, B, P>;
+
+    fn new_account(&self) -> Self::Account {
+        let index = self.next.get();
+        assert!(index < u32::MAX, "too many accounts in slab");
+        self.next.set(index + 1);
+        SlabAccount {
+            balance: SlotBalance {
+                balance: B::default(),
+                occupied: AtomicBool::new(false),
+            },
+            permits: P::default(),
+            slab: self.slab,
+            index,
+            slot: UnsafeCell::new(MaybeUninit::uninit()),
+            _lifetime: PhantomData,
+        }
+    }
+}
+
+unsafe impl<T: Send, B: Balance + Default + Send, P: Permits + Default + Send> Send
+    for SlabAllocator<T, B, P>
+{
+}
+unsafe impl<T: Send + Sync, B: Balance + Default + Sync, P: Permits + Default + Sync> Sync
+    for SlabAllocator<T, B, P>
+{
+}
+
+impl<T, B: Balance + Default, P: Permits + Default> Default for SlabAllocator<T, B, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, B: Balance + Default, P: Permits + Default> SlabAllocator<T, B, P> {
+    pub fn new() -> Self {
+        let mut slab = Box::<Slab<T, B, P>>::new_uninit();
+        let ptr = NonNull::from_mut(&mut *slab).cast();
+        Self(Box::write(
+            slab,
+            Slab {
+                lock: P::default(),
+                book: UnsafeCell::new(AccountBook::new(SlabAccounts {
+                    slab: ptr,
+                    next: Cell::new(0),
+                })),
+                table: IndexTable::new(),
+            },
+        ))
+    }
+
+    /// Allocate a ralc whose lifetime is bound to this slab.
+    pub fn ralc(&self, value: T) -> RalcBox<T, SlabAccount<'_, T, B, P>, InPlace> {
+        let account = self.allocate();
+        let slot = account.slot();
+        unsafe {
+            // SAFETY:
+            // 1. The slot of a free account holds no payload
+            Payload::write(slot, value, InPlace);
+        }
+
+        let owned = unsafe {
+            // SAFETY:
+            // 1. Freshly allocated
+            // 2. Freshly allocated or returned to the free list non-exhausted,
+            //    with all permits released
+            // 3. Written just above, and the slot is only reused with its account
+            RalcBox::from_payload(account, slot)
+        };
+        account.balance.occupied.store(true, Ordering::Release);
+        owned
+    }
+
+    /// Find the ralc an index handle refers to, unless it is stale.
+    pub fn get(
+        &self,
+        index: RalcIndex<T>,
+    ) -> Option<RalcPtr<T, SlabAccount<'_, T, B, P>, InPlace>> {
+        let account = self.0.table.get(index.index())?;
+        let account = unsafe {
+            // SAFETY:
+            // 1. Inserted by `allocate`, and the chunks of the book live as long as the
+            //    slab, which `SlabAccount<'_, T, B, P>` borrows
+            &*account.as_ref().cast_lifetime()
+        };
+        let count = account.resolve(index.generation())?;
+
+        Some(RalcPtr(unsafe {
+            // SAFETY:
+            // 1. Resolved as the count of the ralc in the slot while it was owned
+            RalcRaw::from_generation(AccPtr::new(account), count, account.slot())
+        }))
+    }
+
+    /// Set the number of slots allocated in the next chunk, and
+    /// the limit to which subsequent chunk sizes may grow.
+    pub fn set_chunks(&self, chunk: usize, limit: usize) {
+        self.0.with_book(|book| book.set_chunks(chunk, limit))
+    }
+
+    /// Number of slots in this slab currently holding data.
+    pub fn outstanding(&self) -> usize {
+        self.0.with_book(|book| book.outstanding())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn expansions(&self) -> usize {
+        self.0.with_book(|book| book.expansions())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn free_count(&self) -> usize {
+        self.0.with_book(|book| book.free_count())
+    }
+
+    fn allocate(&self) -> AccPtr<SlabAccount<'_, T, B, P>> {
+        let account = self.0.with_book(|book| {
+            let account = book.allocate();
+            unsafe {
+                // SAFETY:
+                // 1. The table is only written to under the lock
+                self.0.table.insert(account.index, NonNull::from(&*account));
+            }
+            account
+        });
+        unsafe {
+            // SAFETY:
+            // 1.3 The chunks of the book live as long as the slab,
+            //     which `SlabAccount<'_, T, B, P>` borrows
+            AccPtr::new(&*account.cast_lifetime())
+        }
+    }
+}
+
+impl<T, B: Balance + Default, P: Permits + Default> Slab<T, B, P> {
+    fn with_book<X>(&self, f: impl FnOnce(&mut AccountBook<SlabAccounts<T, B, P>>) -> X) -> X {
+        while !self.lock.try_mutation() {
+            std::hint::spin_loop();
+        }
+
+        let res = f(unsafe {
+            // SAFETY:
+            // 1. The mutation permit on `lock` grants exclusive access,
+            //    and `f` never reenters the slab
+            &mut *self.book.get()
+        });
+
+        unsafe {
+            // SAFETY:
+            // 1. Acquired above
+            self.lock.abandon_mutation();
+        }
+        res
+    }
+}
+
+impl<T, B: Balance + Default, P: Permits + Default> RalcBox<T, SlabAccount<'_, T, B, P>, InPlace> {
+    /// An 8-byte handle to this ralc, which its slab can resolve.
+    pub fn index(&self) -> RalcIndex<T> {
+        RalcIndex::new(self.0.account().index, self.0.generation())
+    }
+}
+
+impl<T, B: Balance + Default, P: Permits + Default> RalcPtr<T, SlabAccount<'_, T, B, P>, InPlace> {
+    /// An 8-byte handle to this ralc, which its slab can resolve.
+    pub fn index(&self) -> RalcIndex<T> {
+        RalcIndex::new(self.0.account().index, self.0.generation())
+    }
+}
+
+/// Account type for ralcs allocated from a [`SlabAllocator`], borrowing it for `'a`.
+/// It holds the data of its ralc inline, so that its ralcs need not point to the data
+/// and are only two words.
+pub struct SlabAccount<'a, T, B: Balance + Default, P: Permits + Default> {
+    balance: SlotBalance<B>,
+    permits: P,
+    slab: NonNull<Slab<T, B, P>>,
+    index: u32,
+    slot: UnsafeCell<MaybeUninit<Payload<InPlace, T>>>,
+    _lifetime: PhantomData<&'a ()>,
+}
+
+/// The balance of a slab account, which also tracks whether its slot holds an owned ralc,
+/// so that index handles never resolve to a disowned or free slot.
+///
+/// Owned ralcs have even counts, as slab accounts start out at zero and are only ever
+/// disowned and freed, once each.
+pub struct SlotBalance<B> {
+    balance: B,
+    /// Set once the slot holds an owned ralc, and cleared before it is disowned.
+    occupied: AtomicBool,
+}
+
+// SAFETY:
+// 1. Delegated implementation
+// 2. Delegated implementation
+unsafe impl<B: Balance> Balance for SlotBalance<B> {
+    #[inline]
+    fn invalidate(&self) {
+        if self.balance.check().is_multiple_of(2) {
+            self.occupied.store(false, Ordering::Relaxed);
+            // Pairs with the fence in `SlabAccount::resolve`.
+            fence(Ordering::Release);
+        }
+        self.balance.invalidate();
+    }
+
+    #[inline]
+    fn exhausted(&self) -> bool {
+        self.balance.exhausted()
+    }
+
+    #[inline]
+    fn check(&self) -> u64 {
+        self.balance.check()
+    }
+
+    #[inline]
+    fn poison(&self) {
+        self.balance.poison();
+    }
+
+    #[inline]
+    fn is_poisoned(&self) -> bool {
+        self.balance.is_poisoned()
+    }
+
+    #[inline]
+    fn clear_poison(&self) {
+        self.balance.clear_poison();
+    }
+}
+
+unsafe impl<T: Send + Sync, B: Balance + Default + Sync, P: Permits + Default + Sync> Sync
+    for SlabAccount<'_, T, B, P>
+{
+}
+
+impl<'a, T, B: Balance + Default, P: Permits + Default> SlabAccount<'a, T, B, P> {
+    fn slot(&self) -> NonNull<Payload<InPlace, T>> {
+        unsafe {
+            // SAFETY:
+            // 1. Never null
+            NonNull::new_unchecked(self.slot.get()).cast()
+        }
+    }
+
+    /// The count of the ralc in the slot, if it is owned and its count matches `generation`,
+    /// as packed by [`RalcIndex::new`].
+    fn resolve(&self, generation: u32) -> Option<u64> {
+        let count = self.balance.check();
+        // If the count was read after the ralc was disowned, the cleared flag is seen.
+        fence(Ordering::Acquire);
+        let occupied = self.balance.occupied.load(Ordering::Acquire);
+        (occupied && count.is_multiple_of(2) && (count >> 1) as u32 == generation).then_some(count)
+    }
+
+    /// The same account, as borrowing the slab for a different lifetime.
+    fn cast_lifetime<'b>(&self) -> *const SlabAccount<'b, T, B, P> {
+        (self as *const Self).cast()
+    }
+}
+
+impl<T, B: Balance + Default, P: Permits + Default> DelegateAccountImpl
+    for SlabAccount<'_, T, B, P>
+{
+    type DelegatedBalance = SlotBalance<B>;
+
+    type DelegatedPermits = P;
+
+    fn balance(&self) -> &Self::DelegatedBalance {
+        &self.balance
+    }
+
+    fn permits(&self) -> &Self::DelegatedPermits {
+        &self.permits
+    }
+}
+
+delegate_account_impl!(['a, T, B: Balance + Default, P: Permits + Default] SlabAccount<'a, T, B, P>);
+
+unsafe impl<T, B: Balance + Default, P: Permits + Default> Freeable for SlabAccount<'_, T, B, P> {
+    unsafe fn free(&self) {
+        unsafe {
+            // SAFETY:
+            // Guaranteed by caller.
+            self.abandon_mutation();
+        }
+
+        let slab = unsafe {
+            // SAFETY:
+            // 1. The account borrows the slab, which is boxed and so never moves
+            self.slab.as_ref()
+        };
+        slab.with_book(|book| unsafe {
+            // SAFETY:
+            // 1. Allocated by this slab's book, and only the lifetime differs
+            // 2. Guaranteed by caller, and the data in the slot was dropped before
+            // 3. Guaranteed by caller
+            book.deallocate(AccPtr::new(&*self.cast_lifetime()))
+        });
+        // IMPL SAFETY:
+        // 1. See above
+    }
+}
+
+impl<T, B: Balance + Default, P: Permits + Default> Account for SlabAccount<'_, T, B, P> {
+    type Data = InAccount;
+}
+
+// SAFETY:
+// 1. Slab ralcs only ever keep their data in the slot, so that its pointer need not be
+//    stored, as it is always that of the slot
+unsafe impl<T, B: Balance + Default, P: Permits + Default> DataSlot for SlabAccount<'_, T, B, P> {
+    #[inline]
+    unsafe fn set_data(&self, data: ErasedPtr) {
+        debug_assert_eq!(
+            unsafe {
+                // SAFETY:
+                // 1. Slab ralcs only ever track payloads of `T` in place
+                data.get::<Payload<InPlace, T>>()
+            },
+            self.slot()
+        );
+    }
+
+    #[inline]
+    fn data(&self) -> ErasedPtr {
+        ErasedPtr::new(self.slot())
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    cell::{Cell, RefCell},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use ralc_internals::{
+    accounts::{
+        AccPtr, Account,
+        freeable::Freeable,
+        permits::Permits,
+        slots::{DataSlot, ErasedPtr, InAccount},
+    },
+    delegate_account_impl,
+    delegate_impl::DelegateAccountImpl,
+};
+
+use crate::{ImplicitAccount, ledgers::LeakyBook};
+
+static NEXT_SCOPE: AtomicU64 = AtomicU64::new(1);
+
+tokio::task_local! {
+    static TASK_LOCAL_SCOPE: Scope;
+}
+
+thread_local! {
+    static TASK_LOCAL_ALLOCATOR: RefCell<LeakyBook<TaskLocalAccount>> = const { RefCell::new(LeakyBook::new(TaskLocalAccount::new)) };
+}
+
+/// Account type for ralcs which live for the duration of a tokio task.
+///
+/// Task-local ralcs take no synchronized locks and are never `Send`, which
+/// also confines any future holding one across an `.await` to the thread
+/// it was created on. They can only be allocated inside a future wrapped
+/// with [`FutureExt::with_ralcs`]; allocating one elsewhere panics.
+///
+/// Accounts are drawn from, and returned to, a free list belonging to the
+/// current thread. Handles which are still outstanding when the scope ends
+/// remain valid, and their accounts are returned to the free list whenever
+/// they are eventually dropped, so nothing is deallocated out from under them.
+pub type TaskLocal = TaskLocalAccount;
+
+/// Extension trait for running a future with a scope for [`TaskLocal`] ralcs.
+pub trait FutureExt: Future + Sized {
+    /// Run this future in a fresh task-local ralc scope.
+    fn with_ralcs(self) -> impl Future<Output = Self::Output> {
+        let scope = Scope {
+            id: NEXT_SCOPE.fetch_add(1, Ordering::Relaxed),
+            outstanding: Cell::new(0),
+        };
+        TASK_LOCAL_SCOPE.scope(scope, self)
+    }
+}
+
+impl<F: Future + Sized> FutureExt for F {}
+
+/// Handle for inspecting the allocator of [`TaskLocal`] accounts.
+pub struct TaskLocalAllocator;
+
+impl TaskLocalAllocator {
+    /// Number of task-local accounts allocated in the current scope which are
+    /// still tracking an allocation, or `None` outside of [`FutureExt::with_ralcs`].
+    pub fn outstanding() -> Option<usize> {
+        TASK_LOCAL_SCOPE
+            .try_with(|scope| scope.outstanding.get())
+            .ok()
+    }
+
+    /// Set the number of accounts allocated in the next chunk on this thread,
+    /// and the limit to which subsequent chunk sizes may grow.
+    pub fn set_chunks(chunk: usize, limit: usize) {
+        TASK_LOCAL_ALLOCATOR.with_borrow_mut(|book| book.set_chunks(chunk, limit))
+    }
+
+    #[cfg(test)]
+    pub(crate) fn total_allocations() -> usize {
+        TASK_LOCAL_ALLOCATOR.with_borrow(|book| book.total_allocations())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn expansions() -> usize {
+        TASK_LOCAL_ALLOCATOR.with_borrow(|book| book.expansions())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn free_count() -> usize {
+        TASK_LOCAL_ALLOCATOR.with_borrow(|book| book.free_count())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn reset() {
+        TASK_LOCAL_ALLOCATOR.with_borrow_mut(|book| book.reset())
+    }
+}
+
+struct Scope {
+    id: u64,
+    outstanding: Cell<usize>,
+}
+
+pub struct TaskLocalAccount(Cell<i32>, Cell<u32>, Cell<u64>, Cell<ErasedPtr>);
+
+impl TaskLocalAccount {
+    const fn new() -> Self {
+        Self(
+            Cell::new(0),
+            Cell::new(0),
+            Cell::new(0),
+            Cell::new(ErasedPtr::DANGLING),
+        )
+    }
+}
+
+impl DelegateAccountImpl for TaskLocalAccount {
+    type DelegatedBalance = Cell<i32>;
+
+    type DelegatedPermits = Cell<u32>;
+
+    fn balance(&self) -> &Self::DelegatedBalance {
+        &self.0
+    }
+
+    fn permits(&self) -> &Self::DelegatedPermits {
+        &self.1
+    }
+}
+
+delegate_account_impl!(TaskLocalAccount);
+
+unsafe impl Freeable for TaskLocalAccount {
+    unsafe fn free(&self) {
+        unsafe {
+            // SAFETY:
+            // Guaranteed by caller.
+            self.abandon_mutation();
+        }
+
+        let _ = TASK_LOCAL_SCOPE.try_with(|scope| {
+            if scope.id == self.2.get() {
+                scope.outstanding.update(|n| n - 1);
+            }
+        });
+
+        // If the thread's allocator is already torn down, the account is leaked.
+        let _ = TASK_LOCAL_ALLOCATOR.try_with(|book| unsafe {
+            // SAFETY:
+            // 1. Task-local accounts are only allocated by the book of the thread
+            //    they are confined to, and the book was not torn down since
+            // 2. Guaranteed by caller
+            // 3. Guaranteed by caller
+            book.borrow_mut().deallocate(AccPtr::new(self))
+        });
+        // IMPL SAFETY:
+        // 1. See above
+    }
+}
+
+impl Account for TaskLocalAccount {
+    type Data = InAccount;
+}
+
+// SAFETY:
+// 1. Delegated implementation
+unsafe impl DataSlot for TaskLocalAccount {
+    #[inline]
+    unsafe fn set_data(&self, data: ErasedPtr) {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.3.set_data(data)
+        }
+    }
+
+    #[inline]
+    fn data(&self) -> ErasedPtr {
+        self.3.data()
+    }
+}
+
+impl ImplicitAccount for TaskLocalAccount {
+    fn allocate() -> AccPtr<Self> {
+        let id = TASK_LOCAL_SCOPE
+            .try_with(|scope| {
+                scope.outstanding.update(|n| n + 1);
+                scope.id
+            })
+            .expect("task-local ralcs must be allocated within `FutureExt::with_ralcs`");
+
+        let account = TASK_LOCAL_ALLOCATOR
+            .try_with(|book| book.borrow_mut().allocate())
+            .unwrap_or_else(|_| unsafe {
+                // SAFETY:
+                // 1.1 Leaked
+                AccPtr::new(Box::leak(Box::new(TaskLocalAccount::new())))
+            });
+        account.2.set(id);
+        account
+    }
+}
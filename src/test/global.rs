@@ -0,0 +1,511 @@
+use std::rc::Rc;
+
+use crate::{Global, GlobalAllocator, RalcMut, RalcPtr, RalcRef, RalcUpgradable};
+
+use super::*;
+
+pub(super) static MUTEX: parking_lot::Mutex<()> = parking_lot::Mutex::new(());
+
+#[test]
+fn predictable_allocation_count_global() {
+    let _lock = MUTEX.lock();
+    predictable_allocation_count(
+        RalcBox::<_, Global>::new,
+        GlobalAllocator::reset,
+        GlobalAllocator::total_allocations,
+        GlobalAllocator::free_count,
+    );
+}
+
+#[test]
+fn borrows_dont_allocate_global() {
+    let _lock = MUTEX.lock();
+    borrows_dont_allocate(
+        RalcBox::<_, Global>::new,
+        GlobalAllocator::reset,
+        GlobalAllocator::total_allocations,
+    );
+}
+
+#[test]
+fn test_global_write_read() {
+    let _lock = MUTEX.lock();
+    test_write_read(RalcBox::<_, Global>::new(0));
+}
+
+#[test]
+fn test_global_into_inner() {
+    let _lock = MUTEX.lock();
+    test_into_inner(RalcBox::<_, Global>::new(0));
+}
+
+#[test]
+fn handles_are_two_words_global() {
+    let _lock = MUTEX.lock();
+    handles_are_two_words(RalcBox::<_, Global>::from_box);
+}
+
+#[test]
+fn chunks_grow_to_limit() {
+    let _lock = MUTEX.lock();
+    GlobalAllocator::reset();
+    GlobalAllocator::set_chunks(4, 8);
+
+    let vec = (0..4 + 8 + 8)
+        .map(RalcBox::<_, Global>::new)
+        .collect::<Vec<_>>();
+    assert_eq!(GlobalAllocator::expansions(), 3);
+    assert_eq!(GlobalAllocator::free_count(), 0);
+    std::mem::drop(vec);
+    assert_eq!(GlobalAllocator::free_count(), 20);
+
+    GlobalAllocator::set_chunks(CHUNK_SIZE, CHUNK_SIZE * CHUNK_SIZE);
+}
+
+#[test]
+fn exhausted_accounts_are_retired() {
+    let _lock = MUTEX.lock();
+    GlobalAllocator::reset();
+
+    // Only a free account can be given a balance, or its ralc would count as freed.
+    let ptr = RalcBox::<_, Global>::new(0).borrow();
+    ptr.account().set_balance(0xFF_FFFF_FFFF_FFFE);
+    let owned = RalcBox::<_, Global>::new(0);
+    assert!(std::ptr::eq(owned.account(), ptr.account()));
+    let free = GlobalAllocator::free_count();
+    std::mem::drop(owned);
+    assert_eq!(GlobalAllocator::free_count(), free);
+}
+
+#[test]
+fn shared_across_threads() {
+    let _lock = MUTEX.lock();
+    let owned = RalcBox::<_, Global>::new(0);
+    let ptr = owned.borrow();
+
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(move || {
+                for _ in 0..1000 {
+                    loop {
+                        if let Ok(mut wr) = ptr.try_write() {
+                            *wr += 1;
+                            break;
+                        }
+                        std::hint::spin_loop();
+                    }
+                }
+            });
+        }
+    });
+
+    assert_eq!(*owned.try_read().unwrap(), 4000);
+}
+
+#[test]
+fn blocking_read_waits_for_writer() {
+    let _lock = MUTEX.lock();
+    let owned = RalcBox::<_, Global>::new(0);
+    let ptr = owned.borrow();
+    let barrier = std::sync::Barrier::new(2);
+
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let mut wr = ptr.try_write().unwrap();
+            barrier.wait();
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            *wr += 1;
+        });
+
+        barrier.wait();
+        assert_eq!(*ptr.read().unwrap(), 1);
+        *ptr.write().unwrap() += 1;
+    });
+
+    assert_eq!(*owned.read().unwrap(), 2);
+}
+
+#[test]
+fn blocking_write_revalidates() {
+    let _lock = MUTEX.lock();
+    let owned = RalcBox::<_, Global>::new(0);
+    let ptr = owned.borrow();
+    let barrier = std::sync::Barrier::new(2);
+
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let wr = owned.try_write().unwrap();
+            barrier.wait();
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            // The data lingers until the writer is done with it.
+            std::mem::drop(owned);
+            std::mem::drop(wr);
+        });
+
+        barrier.wait();
+        assert_eq!(ptr.write().unwrap_err(), NoAccess::Stale);
+    });
+}
+
+#[test]
+fn timed_access_times_out() {
+    use std::time::{Duration, Instant};
+
+    let _lock = MUTEX.lock();
+    let owned = RalcBox::<_, Global>::new(0);
+    let ptr = owned.borrow();
+
+    let wr = owned.try_write().unwrap();
+    let start = Instant::now();
+    assert_eq!(
+        ptr.try_read_for(Duration::from_millis(20)).unwrap_err(),
+        NoAccess::Timeout
+    );
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    assert_eq!(
+        ptr.try_write_until(Instant::now() + Duration::from_millis(5))
+            .unwrap_err(),
+        NoAccess::Timeout
+    );
+    assert_eq!(
+        owned.try_read_for(Duration::ZERO).unwrap_err(),
+        NoAccess::Timeout
+    );
+    std::mem::drop(wr);
+
+    let rd = ptr.try_read_for(Duration::ZERO).unwrap();
+    assert_eq!(
+        owned.try_write_for(Duration::from_millis(5)).unwrap_err(),
+        NoAccess::Timeout
+    );
+    std::mem::drop(rd);
+    *owned.try_write_for(Duration::MAX).unwrap() += 1;
+    assert_eq!(*ptr.try_read_until(Instant::now()).unwrap(), 1);
+}
+
+#[test]
+fn timed_access_waits_and_revalidates() {
+    use std::time::Duration;
+
+    let _lock = MUTEX.lock();
+    let owned = RalcBox::<_, Global>::new(0);
+    let ptr = owned.borrow();
+    let barrier = std::sync::Barrier::new(2);
+
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let mut wr = owned.try_write().unwrap();
+            barrier.wait();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            *wr += 1;
+            std::mem::drop(wr);
+
+            barrier.wait();
+            let wr = owned.try_write().unwrap();
+            barrier.wait();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            std::mem::drop(owned);
+            std::mem::drop(wr);
+        });
+
+        barrier.wait();
+        assert_eq!(*ptr.try_read_for(Duration::from_secs(10)).unwrap(), 1);
+        barrier.wait();
+        barrier.wait();
+        assert_eq!(
+            ptr.try_write_for(Duration::from_secs(10)).unwrap_err(),
+            NoAccess::Stale
+        );
+    });
+}
+
+#[test]
+fn upgradable_waits_for_readers() {
+    let _lock = MUTEX.lock();
+    let owned = RalcBox::<_, Global>::new(0);
+    let ptr = owned.borrow();
+    let barrier = std::sync::Barrier::new(2);
+
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let rd = ptr.try_read().unwrap();
+            barrier.wait();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            assert_eq!(*rd, 0);
+        });
+
+        barrier.wait();
+        let up = ptr.upgradable_read().unwrap();
+        let up = up.try_into_write().unwrap_err();
+        *up.into_write().unwrap() += 1;
+    });
+
+    let up = owned.try_upgradable_read().unwrap();
+    let rd = ptr.try_read().unwrap();
+    assert_eq!(ptr.try_upgradable_read().unwrap_err(), NoAccess::Blocked);
+    assert_eq!(ptr.try_write().unwrap_err(), NoAccess::Blocked);
+    std::mem::drop(rd);
+    let up = up.try_into_write().unwrap().into_upgradable();
+    assert_eq!(*up.into_read(), 1);
+}
+
+#[test]
+#[cfg(feature = "deadlock-detection")]
+fn waiting_for_own_permits_deadlocks() {
+    use std::time::Duration;
+
+    use crate::{AccountId, Result};
+
+    fn deadlocked<G>(res: Result<G>, account: AccountId) -> bool {
+        matches!(res, Err(NoAccess::Deadlock(Some(id))) if id == account)
+    }
+
+    let _lock = MUTEX.lock();
+    let owned = RalcBox::<_, Global>::new(0);
+    let ptr = owned.borrow();
+    let id = owned.account_id();
+    assert_eq!(id, ptr.account_id());
+
+    let wr = owned.try_write().unwrap();
+    assert!(deadlocked(ptr.read(), id));
+    assert!(deadlocked(owned.write(), id));
+    assert!(deadlocked(ptr.upgradable_read(), id));
+    // Timed waits give up on their own.
+    assert_eq!(
+        ptr.try_read_for(Duration::from_millis(5)).unwrap_err(),
+        NoAccess::Timeout
+    );
+
+    let up = wr.into_upgradable();
+    assert!(deadlocked(ptr.write(), id));
+    assert!(deadlocked(owned.upgradable_read(), id));
+    let rd = ptr.read().unwrap();
+    let up = up.into_write().unwrap_err();
+    std::mem::drop(rd);
+    let rd = up.into_write().unwrap().into_read();
+    assert!(deadlocked(owned.write(), id));
+    let rd2 = ptr.read().unwrap();
+
+    std::mem::drop((rd, rd2));
+    *ptr.write().unwrap() += 1;
+    assert_eq!(*owned.read().unwrap(), 1);
+}
+
+#[test]
+#[cfg(feature = "deadlock-detection")]
+fn readers_do_not_make_way_for_writers_waiting_on_them() {
+    let _lock = MUTEX.lock();
+    let owned = RalcBox::<_, Global>::new(0);
+    let ptr = owned.borrow();
+
+    let rd = ptr.read().unwrap();
+    std::thread::scope(|s| {
+        s.spawn(|| *ptr.write().unwrap() += 1);
+        // Let the writer start waiting for the reader.
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(*ptr.read().unwrap(), 0);
+        assert_eq!(*owned.read().unwrap(), 0);
+        std::mem::drop(rd);
+    });
+    assert_eq!(*ptr.read().unwrap(), 1);
+}
+
+#[test]
+#[cfg(feature = "deadlock-detection")]
+fn permits_released_elsewhere_do_not_deadlock() {
+    let _lock = MUTEX.lock();
+    let owned = RalcBox::<_, Global>::new(0);
+    let ptr = owned.borrow();
+
+    let wr = owned.try_write().unwrap();
+    std::thread::spawn(move || {
+        let mut wr = wr;
+        *wr += 1;
+    })
+    .join()
+    .unwrap();
+    assert_eq!(*ptr.read().unwrap(), 1);
+
+    let other = RalcBox::<_, Global>::new(0);
+    let _wr = other.try_write().unwrap();
+    let barrier = std::sync::Barrier::new(2);
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let mut wr = ptr.try_write().unwrap();
+            barrier.wait();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            *wr += 1;
+        });
+
+        barrier.wait();
+        assert_eq!(*ptr.read().unwrap(), 2);
+    });
+
+    // A guard released on another thread which holds its own must not take the
+    // entry of that one, leaving its own behind.
+    let rd = ptr.read().unwrap();
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let own = ptr.read().unwrap();
+            std::mem::drop(rd);
+            barrier.wait();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            std::mem::drop(own);
+        });
+
+        barrier.wait();
+        *ptr.write().unwrap() += 1;
+    });
+    assert_eq!(*ptr.read().unwrap(), 3);
+}
+
+#[test]
+fn async_write_waits_for_writer() {
+    let _lock = MUTEX.lock();
+    let owned = RalcBox::<_, Global>::new(0);
+    let ptr = owned.borrow();
+    let barrier = std::sync::Barrier::new(2);
+
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let mut wr = ptr.try_write().unwrap();
+            barrier.wait();
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            *wr += 1;
+        });
+
+        barrier.wait();
+        block_on(async {
+            *ptr.write_async().await.unwrap() += 1;
+            assert_eq!(*ptr.read_async().await.unwrap(), 2);
+        });
+    });
+}
+
+#[test]
+fn async_read_revalidates() {
+    let _lock = MUTEX.lock();
+    let owned = RalcBox::<_, Global>::new(0);
+    let ptr = owned.borrow();
+    let barrier = std::sync::Barrier::new(2);
+
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let wr = owned.try_write().unwrap();
+            barrier.wait();
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            std::mem::drop(owned);
+            std::mem::drop(wr);
+        });
+
+        barrier.wait();
+        assert_eq!(block_on(ptr.read_async()).unwrap_err(), NoAccess::Stale);
+    });
+}
+
+#[test]
+fn async_cancellation_deregisters() {
+    use ralc_internals::accounts::permits::Permits;
+    use std::task::{Context, Poll, Waker};
+
+    let _lock = MUTEX.lock();
+    let owned = RalcBox::<_, Global>::new(0);
+    let ptr = owned.borrow();
+    let queue = owned.account().wait_queue().unwrap();
+    let mut cx = Context::from_waker(Waker::noop());
+
+    let wr = owned.try_write().unwrap();
+    let mut read = Box::pin(ptr.read_async());
+    let mut write = Box::pin(ptr.write_async());
+    assert!(read.as_mut().poll(&mut cx).is_pending());
+    assert!(write.as_mut().poll(&mut cx).is_pending());
+    assert!(read.as_mut().poll(&mut cx).is_pending());
+    assert_eq!(queue.len(), 2);
+
+    std::mem::drop(read);
+    assert_eq!(queue.len(), 1);
+
+    // Releasing the permit wakes all waiters, after which they may be cancelled freely.
+    std::mem::drop(wr);
+    assert!(queue.is_empty());
+    let Poll::Ready(Ok(mut wr)) = write.as_mut().poll(&mut cx) else {
+        panic!("permit was released");
+    };
+    *wr += 1;
+    std::mem::drop(wr);
+    std::mem::drop(write);
+    assert!(queue.is_empty());
+    assert_eq!(*owned.try_read().unwrap(), 1);
+}
+
+#[test]
+fn async_futures_are_send() {
+    use assert_impl::assert_impl;
+    assert_impl!(Send: crate::ReadAsync<i32, Global>, crate::WriteAsync<i32, Global>);
+}
+
+#[test]
+fn magazine_refills_and_flushes_in_batches() {
+    let _lock = MUTEX.lock();
+    GlobalAllocator::reset();
+
+    std::mem::drop((0..20).map(RalcBox::<_, Global>::new).collect::<Vec<_>>());
+    assert_eq!(GlobalAllocator::free_count(), 20);
+    let expansions = GlobalAllocator::expansions();
+
+    GlobalAllocator::set_magazine_size(8);
+    // Joining the thread, unlike leaving a scope, waits for its thread-locals to be dropped.
+    std::thread::spawn(|| {
+        let owned = RalcBox::<_, Global>::new(0);
+        assert_eq!(GlobalAllocator::free_count(), 16);
+        assert_eq!(GlobalAllocator::cached(), 3);
+        std::mem::drop(owned);
+        assert_eq!(GlobalAllocator::cached(), 4);
+
+        let vec = (0..20).map(RalcBox::<_, Global>::new).collect::<Vec<_>>();
+        assert_eq!(GlobalAllocator::free_count(), 0);
+        std::mem::drop(vec);
+        assert!(GlobalAllocator::cached() <= 8);
+        assert_eq!(
+            GlobalAllocator::free_count() + GlobalAllocator::cached(),
+            20
+        );
+    })
+    .join()
+    .unwrap();
+    GlobalAllocator::set_magazine_size(0);
+
+    // The thread's cached accounts were returned when it exited.
+    assert_eq!(GlobalAllocator::free_count(), 20);
+    assert_eq!(GlobalAllocator::expansions(), expansions);
+}
+
+#[test]
+fn magazine_flush() {
+    let _lock = MUTEX.lock();
+    GlobalAllocator::reset();
+    GlobalAllocator::set_magazine_size(8);
+
+    std::mem::drop(RalcBox::<_, Global>::new(0));
+    assert_eq!(GlobalAllocator::cached(), 1);
+    assert_eq!(GlobalAllocator::free_count(), 0);
+    GlobalAllocator::flush_magazine();
+    assert_eq!(GlobalAllocator::cached(), 0);
+    assert_eq!(GlobalAllocator::free_count(), 1);
+
+    // A disabled magazine passes accounts straight through.
+    GlobalAllocator::set_magazine_size(0);
+    std::mem::drop(RalcBox::<_, Global>::new(0));
+    assert_eq!(GlobalAllocator::cached(), 0);
+    assert_eq!(GlobalAllocator::free_count(), 1);
+}
+
+#[test]
+fn send_sync() {
+    use assert_impl::assert_impl;
+    assert_impl!(Send: RalcBox<i32, Global>, RalcPtr<i32, Global>, RalcRef<i32, Global>, RalcMut<i32, Global>);
+    assert_impl!(Sync: RalcBox<i32, Global>, RalcPtr<i32, Global>, RalcRef<i32, Global>, RalcMut<i32, Global>);
+    assert_impl!(Send: RalcUpgradable<i32, Global>);
+    assert_impl!(Sync: RalcUpgradable<i32, Global>);
+    assert_impl!(!Send: RalcBox<Rc<i32>, Global>);
+}
@@ -0,0 +1,313 @@
+use std::{
+    cell::RefCell,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use crate::{
+    RalcMut, RalcPtr, RalcRef, ThreadLocal, ThreadLocalAccount, ThreadLocalAllocator,
+    thread_local::EXITS,
+};
+
+use super::*;
+
+#[test]
+fn predictable_allocation_count_thread_local() {
+    predictable_allocation_count(
+        RalcBox::<_, ThreadLocal>::new,
+        ThreadLocalAllocator::reset,
+        ThreadLocalAllocator::total_allocations,
+        ThreadLocalAllocator::free_count,
+    );
+}
+
+#[test]
+fn borrows_dont_allocate_thread_local() {
+    borrows_dont_allocate(
+        RalcBox::<_, ThreadLocal>::new,
+        ThreadLocalAllocator::reset,
+        ThreadLocalAllocator::total_allocations,
+    );
+}
+
+#[test]
+fn test_thread_local_write_read() {
+    test_write_read(RalcBox::<_, ThreadLocal>::new(0));
+}
+
+#[test]
+fn test_thread_local_into_inner() {
+    test_into_inner(RalcBox::<_, ThreadLocal>::new(0));
+}
+
+#[test]
+fn handles_are_two_words_thread_local() {
+    handles_are_two_words(RalcBox::<_, ThreadLocal>::from_box);
+}
+
+#[test]
+fn chunks_grow_to_limit_thread_local() {
+    ThreadLocalAllocator::reset();
+    ThreadLocalAllocator::set_chunks(4, 8);
+
+    let vec = (0..4 + 8 + 8)
+        .map(RalcBox::<_, ThreadLocal>::new)
+        .collect::<Vec<_>>();
+    assert_eq!(ThreadLocalAllocator::expansions(), 3);
+    assert_eq!(ThreadLocalAllocator::outstanding(), 20);
+    std::mem::drop(vec);
+    assert_eq!(ThreadLocalAllocator::free_count(), 20);
+    assert_eq!(ThreadLocalAllocator::outstanding(), 0);
+}
+
+#[test]
+fn exhausted_accounts_are_retired_thread_local() {
+    ThreadLocalAllocator::reset();
+
+    // Only a free account can be given a balance, or its ralc would count as freed.
+    let ptr = RalcBox::<_, ThreadLocal>::new(0).borrow();
+    ptr.account().set_balance(i32::MAX - 1);
+    let owned = RalcBox::<_, ThreadLocal>::new(0);
+    assert!(std::ptr::eq(owned.account(), ptr.account()));
+    let free = ThreadLocalAllocator::free_count();
+    std::mem::drop(owned);
+    assert_eq!(ThreadLocalAllocator::free_count(), free);
+    assert_eq!(ThreadLocalAllocator::outstanding(), 0);
+}
+
+#[test]
+fn lingering_readers_count_as_outstanding() {
+    let owned = RalcBox::<_, ThreadLocal>::new(0);
+    let before = ThreadLocalAllocator::outstanding();
+    let rd = owned.try_read().unwrap();
+    std::mem::drop(owned);
+    assert_eq!(ThreadLocalAllocator::outstanding(), before);
+    std::mem::drop(rd);
+    assert_eq!(ThreadLocalAllocator::outstanding(), before - 1);
+}
+
+fn exit_outstanding(thread: std::thread::JoinHandle<()>) -> usize {
+    let id = thread.thread().id();
+    thread.join().unwrap();
+    EXITS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(exited, _)| *exited == id)
+        .map(|(_, outstanding)| *outstanding)
+        .unwrap()
+}
+
+#[test]
+fn no_accounts_outstanding_on_thread_exit() {
+    let thread = std::thread::spawn(|| {
+        let owned = RalcBox::<_, ThreadLocal>::new(0);
+        test_write_read(owned);
+        let ptr = RalcBox::<_, ThreadLocal>::new(0).borrow();
+        assert!(!ptr.check());
+    });
+    assert_eq!(exit_outstanding(thread), 0);
+}
+
+#[test]
+fn forgotten_ralcs_stay_outstanding_on_thread_exit() {
+    let thread = std::thread::spawn(|| {
+        std::mem::forget(RalcBox::<_, ThreadLocal>::new(0));
+        let owned = RalcBox::<_, ThreadLocal>::new(1);
+        std::mem::forget(owned.try_read().unwrap());
+    });
+    assert_eq!(exit_outstanding(thread), 2);
+}
+
+struct CountDrops(Arc<AtomicUsize>);
+
+impl Drop for CountDrops {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+thread_local! {
+    static KEPT: RefCell<Vec<RalcBox<CountDrops, ThreadLocal>>> = const { RefCell::new(Vec::new()) };
+}
+
+#[test]
+fn ralcs_in_thread_locals_survive_teardown() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let thread = std::thread::spawn({
+        let drops = drops.clone();
+        move || {
+            KEPT.with_borrow_mut(|kept| kept.push(RalcBox::new(CountDrops(drops.clone()))));
+            KEPT.with_borrow_mut(|kept| kept.push(RalcBox::new(CountDrops(drops))));
+        }
+    });
+
+    // Thread-local destruction order is unspecified: the book may be torn
+    // down before or after the ralcs kept alongside it.
+    assert!(matches!(exit_outstanding(thread), 0 | 2));
+    assert_eq!(drops.load(Ordering::Relaxed), 2);
+}
+
+/// Checks a weak pointer when torn down, recording whether the allocator was torn down
+/// first and whether the pointer still checked out.
+struct CheckOnDrop(
+    RalcPtr<i32, ThreadLocal>,
+    Arc<std::sync::Mutex<Option<(bool, bool)>>>,
+);
+
+impl Drop for CheckOnDrop {
+    fn drop(&mut self) {
+        let id = std::thread::current().id();
+        let after = EXITS
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|&(exited, _)| exited == id);
+        *self.1.lock().unwrap() = Some((after, self.0.check()));
+    }
+}
+
+thread_local! {
+    static WEAK: RefCell<Option<CheckOnDrop>> = const { RefCell::new(None) };
+}
+
+#[test]
+fn weak_pointers_in_thread_locals_survive_teardown() {
+    let checked = Arc::new(std::sync::Mutex::new(None));
+    let thread = std::thread::spawn({
+        let checked = checked.clone();
+        move || {
+            // Thread-locals are torn down in reverse order of first use, so the
+            // pointer is checked after the allocator is gone.
+            WEAK.with_borrow(|_| {});
+            let ptr = RalcBox::<_, ThreadLocal>::new(0).borrow();
+            WEAK.with_borrow_mut(|weak| *weak = Some(CheckOnDrop(ptr, checked)));
+        }
+    });
+    thread.join().unwrap();
+    assert_eq!(*checked.lock().unwrap(), Some((true, false)));
+}
+
+#[test]
+fn accounts_leaked_on_thread_exit() {
+    use ralc_internals::accounts::balances::Balance;
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let thread = std::thread::spawn({
+        let drops = drops.clone();
+        move || {
+            let owned = RalcBox::<_, ThreadLocal>::new(CountDrops(drops));
+            let ptr = owned.borrow();
+            let generation = ptr.account().check();
+            std::mem::drop(owned);
+            (
+                std::ptr::from_ref(ptr.account()).expose_provenance(),
+                generation,
+            )
+        }
+    });
+    let (account, generation) = thread.join().unwrap();
+
+    // No account was outstanding, yet the account outlived the thread.
+    assert_eq!(drops.load(Ordering::Relaxed), 1);
+    let account = std::ptr::with_exposed_provenance::<ThreadLocalAccount>(account);
+    assert_eq!(unsafe { (*account).check() }, generation + 2);
+}
+
+#[test]
+fn blocking_access_would_deadlock() {
+    let owned = RalcBox::<_, ThreadLocal>::new(0);
+    let ptr = owned.borrow();
+
+    *ptr.write().unwrap() += 1;
+    let wr = owned.try_write().unwrap();
+    assert_eq!(ptr.read().unwrap_err(), NoAccess::Deadlock(None));
+    assert_eq!(ptr.write().unwrap_err(), NoAccess::Deadlock(None));
+    assert_eq!(owned.read().unwrap_err(), NoAccess::Deadlock(None));
+    std::mem::drop(wr);
+
+    let rd = ptr.read().unwrap();
+    assert_eq!(*owned.read().unwrap(), 1);
+    assert_eq!(ptr.write().unwrap_err(), NoAccess::Deadlock(None));
+    std::mem::drop(rd);
+
+    std::mem::drop(owned);
+    assert_eq!(ptr.read().unwrap_err(), NoAccess::Stale);
+}
+
+#[test]
+fn timed_access_is_unsupported() {
+    use std::time::Duration;
+
+    let owned = RalcBox::<_, ThreadLocal>::new(0);
+    let ptr = owned.borrow();
+
+    let wr = owned.try_write().unwrap();
+    assert_eq!(
+        ptr.try_read_for(Duration::from_secs(10)).unwrap_err(),
+        NoAccess::Unsupported
+    );
+    assert_eq!(
+        owned.try_write_for(Duration::from_secs(10)).unwrap_err(),
+        NoAccess::Unsupported
+    );
+    // Timeouts too long for a deadline do not fall back to blocking.
+    assert_eq!(
+        ptr.try_read_for(Duration::MAX).unwrap_err(),
+        NoAccess::Unsupported
+    );
+    assert_eq!(
+        owned.try_write_for(Duration::MAX).unwrap_err(),
+        NoAccess::Unsupported
+    );
+    std::mem::drop(wr);
+
+    *ptr.try_write_for(Duration::from_secs(10)).unwrap() += 1;
+    assert_eq!(*owned.try_read_for(Duration::ZERO).unwrap(), 1);
+}
+
+#[test]
+fn async_access_is_unsupported() {
+    let owned = RalcBox::<_, ThreadLocal>::new(0);
+    let ptr = owned.borrow();
+
+    block_on(async {
+        *ptr.write_async().await.unwrap() += 1;
+        let wr = owned.try_write().unwrap();
+        assert_eq!(ptr.read_async().await.unwrap_err(), NoAccess::Unsupported);
+        std::mem::drop(wr);
+        assert_eq!(*ptr.read_async().await.unwrap(), 1);
+    });
+}
+
+#[test]
+fn not_send() {
+    use assert_impl::assert_impl;
+    assert_impl!(!Send: RalcBox<i32, ThreadLocal>, RalcPtr<i32, ThreadLocal>, RalcRef<i32, ThreadLocal>, RalcMut<i32, ThreadLocal>);
+    assert_impl!(!Sync: RalcBox<i32, ThreadLocal>, RalcPtr<i32, ThreadLocal>);
+}
+
+#[test]
+fn free_list_spans_chunks() {
+    ThreadLocalAllocator::reset();
+    let n = 3 * CHUNK_SIZE + 1;
+
+    for _ in 0..3 {
+        let vec = (0..n)
+            .map(RalcBox::<_, ThreadLocal>::new)
+            .collect::<Vec<_>>();
+        std::mem::drop(vec);
+        assert_eq!(ThreadLocalAllocator::free_count(), n);
+    }
+
+    let expansions = ThreadLocalAllocator::expansions();
+    let vec = (0..n / 2)
+        .map(RalcBox::<_, ThreadLocal>::new)
+        .collect::<Vec<_>>();
+    assert_eq!(ThreadLocalAllocator::free_count(), n - n / 2);
+    std::mem::drop(vec);
+    assert_eq!(ThreadLocalAllocator::free_count(), n);
+    assert_eq!(ThreadLocalAllocator::expansions(), expansions);
+}
@@ -0,0 +1,704 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use ralc_internals::{
+    accounts::{AccPtr, Account, freeable::Freeable, permits::Permits, slots::InRalc},
+    delegate_account_impl,
+    delegate_impl::DelegateAccountImpl,
+};
+
+use crate::{ImplicitAccount, NoAccess, RalcBox, alloc::Allocator, ledgers::CHUNK_SIZE};
+
+#[cfg(feature = "parking-lot")]
+mod global;
+mod local;
+mod pool;
+mod slab;
+mod stress;
+mod task_local;
+
+#[cfg(miri)]
+const N: usize = 100;
+#[cfg(not(miri))]
+const N: usize = 100_000;
+
+/// A leaking, thread-local account for testing the pointer types in isolation.
+struct TestAccount(Cell<u64>, Cell<u32>);
+
+thread_local! {
+    static FREE: RefCell<Vec<AccPtr<TestAccount>>> = const { RefCell::new(Vec::new()) };
+}
+
+impl DelegateAccountImpl for TestAccount {
+    type DelegatedBalance = Cell<u64>;
+
+    type DelegatedPermits = Cell<u32>;
+
+    fn balance(&self) -> &Self::DelegatedBalance {
+        &self.0
+    }
+
+    fn permits(&self) -> &Self::DelegatedPermits {
+        &self.1
+    }
+}
+
+delegate_account_impl!(TestAccount);
+
+unsafe impl Freeable for TestAccount {
+    unsafe fn free(&self) {
+        unsafe {
+            // SAFETY:
+            // Guaranteed by caller.
+            self.abandon_mutation();
+        }
+        FREE.with_borrow_mut(|free| {
+            free.push(unsafe {
+                // SAFETY:
+                // 1.2 Leaked in `allocate`, and `AccPtr<TestAccount>: !Send`
+                AccPtr::new(self)
+            })
+        });
+    }
+}
+
+impl Account for TestAccount {
+    type Data = InRalc;
+}
+
+impl ImplicitAccount for TestAccount {
+    fn allocate() -> AccPtr<Self> {
+        FREE.with_borrow_mut(|free| free.pop())
+            .unwrap_or_else(|| unsafe {
+                // SAFETY:
+                // 1.2 Leaked, and `AccPtr<TestAccount>: !Send`
+                AccPtr::new(Box::leak(Box::new(TestAccount(Cell::new(0), Cell::new(0)))))
+            })
+    }
+}
+
+type TestBox<T, Alloc = crate::alloc::Global> = RalcBox<T, TestAccount, Alloc>;
+
+fn predictable_allocation_count<A: Account>(
+    new: impl Fn(i32) -> RalcBox<i32, A>,
+    reset: impl Fn(),
+    total_allocations: impl Fn() -> usize,
+    free_count: impl Fn() -> usize,
+) {
+    reset();
+    let mut vec = vec![];
+    for i in 0..N {
+        vec.push(new(i as i32))
+    }
+    assert_eq!(N, total_allocations());
+    for or in vec {
+        test_write_read(or);
+    }
+    assert!(free_count() >= N);
+}
+
+fn borrows_dont_allocate<A: Account>(
+    new: impl Fn(i32) -> RalcBox<i32, A>,
+    reset: impl Fn(),
+    total_allocations: impl Fn() -> usize,
+) {
+    reset();
+    let owned = new(0);
+    let mut vec = vec![];
+    for _ in 0..N {
+        vec.push(owned.borrow());
+    }
+    assert_eq!(1, total_allocations());
+    for or in vec {
+        *or.try_write().unwrap() += 1;
+    }
+    assert_eq!(*owned.try_read().unwrap(), N as i32);
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+fn test_write_read(owned: RalcBox<i32, impl Account, impl Allocator>) {
+    let mut wr = owned.try_write().unwrap();
+    *wr = 99;
+    assert_eq!(owned.try_read().unwrap_err(), NoAccess::Blocked);
+    std::mem::drop(wr);
+
+    let rd = owned.try_read().unwrap();
+    assert_eq!(owned.try_write().unwrap_err(), NoAccess::Blocked);
+    let res = *rd;
+    std::mem::drop(rd);
+
+    assert_eq!(res, 99);
+
+    let ptr = owned.borrow();
+    assert!(ptr.check());
+    std::mem::drop(owned);
+    assert!(!ptr.check());
+    assert_eq!(ptr.try_read().unwrap_err(), NoAccess::Stale);
+}
+
+fn test_into_inner(owned: RalcBox<i32, impl Account>) {
+    *owned.try_write().unwrap() = 99;
+
+    let rd = owned.try_read().unwrap();
+    let owned = owned.try_into_box().unwrap_err();
+    std::mem::drop(rd);
+
+    let ptr = owned.borrow();
+    let res = *owned.try_into_box().unwrap();
+    assert_eq!(res, 99);
+    assert!(!ptr.check());
+}
+
+/// Handles are two words, as the account keeps the data pointer, even a wide one.
+fn handles_are_two_words<A: Account>(new: impl Fn(Box<str>) -> RalcBox<str, A>) {
+    use crate::RalcPtr;
+
+    assert_eq!(size_of::<RalcBox<String, A>>(), 16);
+    assert_eq!(size_of::<RalcPtr<String, A>>(), 16);
+    assert_eq!(size_of::<Option<RalcPtr<String, A>>>(), 16);
+    assert_eq!(size_of::<RalcPtr<str, A>>(), 16);
+    // Guards also carry their registry token when detecting deadlocks.
+    #[cfg(not(feature = "deadlock-detection"))]
+    assert_eq!(size_of::<crate::RalcMut<String, A>>(), 16);
+
+    let owned = new("two".into());
+    let other = new("words".into());
+    let ptr = owned.borrow();
+    assert_eq!(&*ptr.try_read().unwrap(), "two");
+    assert_eq!(&*other.try_read().unwrap(), "words");
+    std::mem::drop(owned);
+    let reused = new("reused".into());
+    assert_eq!(ptr.try_read().unwrap_err(), NoAccess::Stale);
+    assert_eq!(&*reused.try_read().unwrap(), "reused");
+}
+
+/// Counts how many times it has been dropped.
+#[derive(Debug)]
+struct DropCount(Rc<Cell<usize>>);
+
+impl Drop for DropCount {
+    fn drop(&mut self) {
+        self.0.update(|n| n + 1);
+    }
+}
+
+#[test]
+fn write_read() {
+    test_write_read(TestBox::new(0));
+}
+
+#[test]
+fn into_inner() {
+    test_into_inner(TestBox::new(0));
+}
+
+#[test]
+fn readers_outlive_owner() {
+    let drops = Rc::new(Cell::new(0));
+    let owned = TestBox::new(DropCount(drops.clone()));
+    let ptr = owned.borrow();
+
+    let rd = ptr.try_read().unwrap();
+    let rd2 = rd.clone();
+    std::mem::drop(owned);
+
+    assert!(!ptr.check());
+    assert_eq!(ptr.try_read().unwrap_err(), NoAccess::Stale);
+    assert_eq!(drops.get(), 0);
+
+    std::mem::drop(rd);
+    assert_eq!(drops.get(), 0);
+    std::mem::drop(rd2);
+    assert_eq!(drops.get(), 1);
+}
+
+#[test]
+fn writer_outlives_owner() {
+    let drops = Rc::new(Cell::new(0));
+    let owned = TestBox::new(DropCount(drops.clone()));
+
+    let wr = owned.borrow().try_write().unwrap();
+    std::mem::drop(owned);
+    assert_eq!(drops.get(), 0);
+
+    let rd = wr.into_read();
+    assert_eq!(drops.get(), 0);
+    std::mem::drop(rd);
+    assert_eq!(drops.get(), 1);
+}
+
+#[test]
+fn upgrade_and_downgrade() {
+    let owned = TestBox::new(0);
+    let ptr = owned.borrow();
+
+    let rd = ptr.try_read().unwrap();
+    let rd2 = rd.clone();
+    let rd = rd.try_into_write().unwrap_err();
+    std::mem::drop(rd2);
+
+    let mut wr = rd.try_into_write().unwrap();
+    *wr += 1;
+    assert_eq!(ptr.try_read().unwrap_err(), NoAccess::Blocked);
+
+    let rd = wr.into_read();
+    assert_eq!(*ptr.try_read().unwrap(), 1);
+    assert_eq!(ptr.try_write().unwrap_err(), NoAccess::Blocked);
+    std::mem::drop(rd);
+
+    assert_eq!(*ptr.try_write().unwrap(), 1);
+}
+
+#[test]
+fn upgradable_coexists_with_readers() {
+    let owned = TestBox::new(0);
+    let ptr = owned.borrow();
+
+    let up = ptr.try_upgradable_read().unwrap();
+    let rd = ptr.try_read().unwrap();
+    assert_eq!(ptr.try_upgradable_read().unwrap_err(), NoAccess::Blocked);
+    assert_eq!(ptr.try_write().unwrap_err(), NoAccess::Blocked);
+    // Readers cannot escalate past an upgradable reference.
+    let rd = rd.try_into_write().unwrap_err();
+
+    let up = up.try_into_write().unwrap_err();
+    let up = up.into_write().unwrap_err();
+    std::mem::drop(rd);
+
+    let mut wr = up.try_into_write().unwrap();
+    *wr += 1;
+    assert_eq!(ptr.try_read().unwrap_err(), NoAccess::Blocked);
+
+    let up = wr.into_upgradable();
+    assert_eq!(*ptr.try_read().unwrap(), 1);
+    assert_eq!(owned.try_upgradable_read().unwrap_err(), NoAccess::Blocked);
+
+    let rd = up.into_read();
+    let up = owned.upgradable_read().unwrap();
+    std::mem::drop(rd);
+    assert_eq!(*up.into_write().unwrap(), 1);
+    assert_eq!(*ptr.try_write().unwrap(), 1);
+}
+
+#[test]
+fn upgradable_outlives_owner() {
+    let drops = Rc::new(Cell::new(0));
+    let owned = TestBox::new(DropCount(drops.clone()));
+    let ptr = owned.borrow();
+
+    let up = ptr.try_upgradable_read().unwrap();
+    let rd = ptr.try_read().unwrap();
+    std::mem::drop(owned);
+    assert_eq!(ptr.try_upgradable_read().unwrap_err(), NoAccess::Stale);
+
+    std::mem::drop(rd);
+    assert_eq!(drops.get(), 0);
+    std::mem::drop(up);
+    assert_eq!(drops.get(), 1);
+
+    let owned = TestBox::new(DropCount(drops.clone()));
+    let up = owned.try_upgradable_read().unwrap();
+    let rd = owned.try_read().unwrap();
+    std::mem::drop(owned);
+
+    std::mem::drop(up);
+    assert_eq!(drops.get(), 1);
+    std::mem::drop(rd);
+    assert_eq!(drops.get(), 2);
+}
+
+#[test]
+fn stale_pointers_ignore_reused_accounts() {
+    let first = TestBox::new(1);
+    let ptr = first.borrow();
+    std::mem::drop(first);
+
+    let second = TestBox::new(2);
+    let ptr2 = second.borrow();
+
+    assert_eq!(ptr.try_read().unwrap_err(), NoAccess::Stale);
+    assert_eq!(ptr.try_write().unwrap_err(), NoAccess::Stale);
+    assert_eq!(*ptr2.try_read().unwrap(), 2);
+    assert_eq!(*second.try_write().unwrap(), 2);
+}
+
+#[test]
+fn raw_parts() {
+    use std::{
+        ptr::NonNull,
+        sync::atomic::{AtomicPtr, AtomicU64, Ordering},
+    };
+
+    use crate::RalcPtr;
+
+    let alloc = CountingAlloc::default();
+    let owned = TestBox::new_in(5, &alloc);
+    assert_eq!(owned.generation(), owned.borrow().generation());
+
+    let (account, generation, data) = owned.borrow().into_raw_parts();
+    let slots = (
+        AtomicPtr::new(account.as_ptr()),
+        AtomicU64::new(generation),
+        AtomicPtr::new(data.as_ptr()),
+    );
+    let rebuild = |generation| unsafe {
+        RalcPtr::<i32, TestAccount, &CountingAlloc>::from_raw_parts(
+            NonNull::new(slots.0.load(Ordering::Relaxed)).unwrap(),
+            generation,
+            NonNull::new(slots.2.load(Ordering::Relaxed)).unwrap(),
+        )
+    };
+
+    let ptr = rebuild(slots.1.load(Ordering::Relaxed)).unwrap();
+    *ptr.try_write().unwrap() += 1;
+    assert_eq!(*owned.try_read().unwrap(), 6);
+    assert!(rebuild(generation + 1).is_none());
+    assert!(rebuild(1 << 56).is_none());
+
+    std::mem::drop(owned);
+    let stale = rebuild(generation).unwrap();
+    assert_eq!(stale.try_read().unwrap_err(), NoAccess::Stale);
+    assert!(rebuild(generation + 3).is_none());
+}
+
+crate::fields! {
+    #[derive(Debug)]
+    struct Pair {
+        left: DropCount,
+        right: Vec<i32>,
+    }
+}
+
+#[test]
+fn mapped_guards() {
+    let drops = Rc::new(Cell::new(0));
+    let owned = TestBox::new(Pair {
+        left: DropCount(drops.clone()),
+        right: vec![1, 2],
+    });
+
+    let mut right = owned.try_write().unwrap().map(|pair| &mut pair.right);
+    right.push(3);
+    assert_eq!(owned.try_read().unwrap_err(), NoAccess::Blocked);
+    let mut last = right.try_map(|right| right.last_mut()).unwrap();
+    *last = 4;
+    std::mem::drop(last);
+
+    let rd = owned.try_read().unwrap();
+    let right = rd.clone().map(|pair| &pair.right);
+    let missing = rd.try_map(|pair| pair.right.get(3)).unwrap_err();
+    assert_eq!(*right, [1, 2, 4]);
+    assert_eq!(format!("{:?}", right.map(|right| &right[..1])), "[1]");
+    assert!(owned.try_write().is_err());
+
+    std::mem::drop(owned);
+    assert_eq!(drops.get(), 0);
+    std::mem::drop(missing);
+    assert_eq!(drops.get(), 1);
+}
+
+#[test]
+fn projected_pointers() {
+    let drops = Rc::new(Cell::new(0));
+    let owned = TestBox::new(Pair {
+        left: DropCount(drops.clone()),
+        right: vec![],
+    });
+    let right = owned.borrow().project(Pair::right);
+    let left = owned.borrow().project(Pair::left);
+
+    right.try_write().unwrap().push(1);
+    assert_eq!(owned.try_read().unwrap().right, [1]);
+
+    let wr = right.try_write().unwrap();
+    assert_eq!(left.try_read().unwrap_err(), NoAccess::Blocked);
+    std::mem::drop(wr);
+
+    let rd = left.try_read().unwrap();
+    assert_eq!(rd.0.get(), 0);
+    std::mem::drop(owned);
+    assert!(!right.check());
+    assert_eq!(right.try_read().unwrap_err(), NoAccess::Stale);
+    assert_eq!(drops.get(), 0);
+    std::mem::drop(rd);
+    assert_eq!(drops.get(), 1);
+
+    let owned = TestBox::new(Pair {
+        left: DropCount(drops.clone()),
+        right: vec![],
+    });
+    let reused = owned.borrow().project(Pair::right);
+    assert_eq!(reused.account_id(), right.account_id());
+    assert_eq!(right.try_write().unwrap_err(), NoAccess::Stale);
+    assert!(reused.try_write().is_ok());
+}
+
+#[derive(Debug, Default)]
+struct Tagged<'a, T>(T, &'a str)
+where
+    T: Default;
+
+crate::fields! {
+    impl<'a, T> Tagged<'a, T>
+    where
+        T: Default,
+    {
+        value = 0: T,
+        tag = 1: &'a str,
+    }
+}
+
+#[test]
+fn projected_generic_pointers() {
+    let owned = TestBox::new(Tagged(vec![1], "tag"));
+    let value = owned.borrow().project(Tagged::value);
+    let tag = owned.borrow().project(Tagged::tag);
+    value.try_write().unwrap().push(2);
+    assert_eq!(*tag.try_read().unwrap(), "tag");
+    assert_eq!(*value.try_read().unwrap(), [1, 2]);
+}
+
+#[test]
+fn split_guards() {
+    let drops = Rc::new(Cell::new(0));
+    let owned = TestBox::new(Pair {
+        left: DropCount(drops.clone()),
+        right: vec![1, 2, 3, 4],
+    });
+    let ptr = owned.borrow();
+
+    let (left, right) = owned
+        .try_write()
+        .unwrap()
+        .map_split(|pair| (&mut pair.left, &mut pair.right));
+    assert_eq!(left.parts(), 2);
+    let mut chunks = right.split_parts(|right| right.chunks_mut(2).collect());
+    assert_eq!(left.parts(), 3);
+    chunks[0][0] = 5;
+    chunks[1].reverse();
+
+    std::mem::drop(owned);
+    std::mem::drop(left);
+    assert_eq!(ptr.try_read().unwrap_err(), NoAccess::Stale);
+    assert_eq!(drops.get(), 0);
+    let last = chunks.pop().unwrap();
+    std::mem::drop(chunks);
+    assert_eq!(*last, [4, 3]);
+    std::mem::drop(last);
+    assert_eq!(drops.get(), 1);
+
+    let owned = TestBox::new((0, 0));
+    let (mut a, b) = owned.try_write().unwrap().map_split(|(a, b)| (a, b));
+    *a += 1;
+    std::mem::drop(a);
+    assert_eq!(owned.try_read().unwrap_err(), NoAccess::Blocked);
+    let mut b = b.map(|b| b);
+    *b += 2;
+    std::mem::drop(b);
+    assert_eq!(*owned.try_read().unwrap(), (1, 2));
+}
+
+#[test]
+fn formatting() {
+    let owned = TestBox::new(5);
+    let ptr = owned.borrow();
+    assert_eq!(format!("{owned:?}"), "RalcBox(5)");
+    assert_eq!(format!("{ptr:#}"), "5 (borrowed)");
+
+    let wr = owned.try_write().unwrap();
+    assert_eq!(format!("{owned}"), "<unavailable>");
+    assert_eq!(format!("{wr:?}"), "5");
+}
+
+#[test]
+fn unsized_payloads() {
+    let slice = TestBox::<[i32]>::from_box(vec![1, 2, 3].into_boxed_slice());
+    let ptr = slice.borrow();
+    ptr.try_write().unwrap()[1] = 5;
+    assert_eq!(&*slice.try_read().unwrap(), [1, 5, 3]);
+    assert_eq!(format!("{slice:?}"), "RalcBox([1, 5, 3])");
+    assert_eq!(*slice.try_into_box().unwrap(), [1, 5, 3]);
+    assert_eq!(ptr.try_read().unwrap_err(), NoAccess::Stale);
+
+    let string = TestBox::<str>::from_box("ralc".into());
+    string.try_write().unwrap().make_ascii_uppercase();
+    assert_eq!(format!("{}", string.borrow()), "RALC");
+
+    let drops = Rc::new(Cell::new(0));
+    let object = TestBox::<dyn std::any::Any>::from_box(Box::new(DropCount(drops.clone())));
+    let rd = object.try_read().unwrap();
+    assert!(rd.is::<DropCount>());
+    std::mem::drop(object);
+    assert_eq!(drops.get(), 0);
+    std::mem::drop(rd);
+    assert_eq!(drops.get(), 1);
+}
+
+#[test]
+#[cfg(feature = "nightly")]
+fn unsized_coercion() {
+    use std::fmt::Display;
+
+    use crate::RalcPtr;
+
+    let owned: TestBox<dyn Display> = TestBox::new(5);
+    let array = TestBox::new([1, 2, 3]);
+    let ptr: RalcPtr<[i32], TestAccount> = array.borrow();
+    assert_eq!(format!("{owned}"), "5");
+    assert_eq!(ptr.try_read().unwrap().len(), 3);
+
+    let slice: TestBox<[i32]> = array;
+    std::mem::drop(slice);
+    assert!(!ptr.check());
+}
+
+/// Counts live allocations made through it.
+#[derive(Default)]
+struct CountingAlloc(Cell<usize>);
+
+unsafe impl ralc_internals::alloc::Allocator for &CountingAlloc {
+    fn allocate(&self, layout: std::alloc::Layout) -> Option<std::ptr::NonNull<u8>> {
+        self.0.update(|n| n + 1);
+        crate::alloc::Global.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: std::ptr::NonNull<u8>, layout: std::alloc::Layout) {
+        self.0.update(|n| n - 1);
+        unsafe { crate::alloc::Global.deallocate(ptr, layout) }
+    }
+}
+
+#[test]
+fn custom_allocators() {
+    let alloc = CountingAlloc::default();
+    let drops = Rc::new(Cell::new(0));
+
+    let owned = TestBox::new_in(DropCount(drops.clone()), &alloc);
+    assert_eq!(alloc.0.get(), 1);
+    let rd = owned.borrow().try_read().unwrap();
+    std::mem::drop(owned);
+    assert_eq!((alloc.0.get(), drops.get()), (1, 0));
+    std::mem::drop(rd);
+    assert_eq!((alloc.0.get(), drops.get()), (0, 1));
+
+    let owned = TestBox::new_in(vec![1, 2], &alloc);
+    owned.try_write().unwrap().push(3);
+    assert_eq!(owned.try_into_inner().unwrap(), [1, 2, 3]);
+    assert_eq!(alloc.0.get(), 0);
+
+    let owned = TestBox::new_in((), &alloc);
+    assert_eq!(alloc.0.get(), 1);
+    assert_eq!(format!("{owned:?}"), "RalcBox(())");
+    std::mem::drop(owned);
+    assert_eq!(alloc.0.get(), 0);
+
+    assert_eq!(TestBox::new(7).try_into_inner().unwrap(), 7);
+}
+
+#[test]
+fn recycled_payloads() {
+    use crate::alloc::Recycling;
+
+    let address = |owned: &TestBox<[u64; 4], Recycling>| {
+        std::ptr::from_ref(&*owned.try_read().unwrap()).addr()
+    };
+
+    let owned = TestBox::new_in([1; 4], Recycling);
+    let first = address(&owned);
+    std::mem::drop(owned);
+    assert_eq!(Recycling::cached(), 32);
+
+    let owned = TestBox::new_in([2; 4], Recycling);
+    assert_eq!(address(&owned), first);
+    assert_eq!(Recycling::cached(), 0);
+    let rd = owned.borrow().try_read().unwrap();
+    std::mem::drop(owned);
+    assert_eq!(Recycling::cached(), 0);
+    assert_eq!(*rd, [2; 4]);
+    std::mem::drop(rd);
+    assert_eq!(Recycling::cached(), 32);
+
+    let owned = TestBox::new_in(1u8, Recycling);
+    assert_eq!(owned.try_into_inner().unwrap(), 1);
+    assert_eq!(Recycling::cached(), 33);
+
+    Recycling::trim();
+    assert_eq!(Recycling::cached(), 0);
+}
+
+#[test]
+fn recycling_shares_size_classes() {
+    use crate::alloc::Recycling;
+
+    Recycling::trim();
+    let owned = TestBox::new_in([1u64; 5], Recycling);
+    let first = std::ptr::from_ref(&*owned.try_read().unwrap()).addr();
+    std::mem::drop(owned);
+    assert_eq!(Recycling::cached(), 48);
+
+    let owned = TestBox::new_in([2u64; 6], Recycling);
+    assert_eq!(
+        std::ptr::from_ref(&*owned.try_read().unwrap()).addr(),
+        first
+    );
+    assert_eq!(Recycling::cached(), 0);
+    std::mem::drop(owned);
+    Recycling::trim();
+}
+
+#[test]
+#[cfg(feature = "recycling")]
+fn new_recycles() {
+    use crate::alloc::Recycling;
+
+    Recycling::trim();
+    let owned = TestBox::new([1u64; 4]);
+    let first = std::ptr::from_ref(&*owned.try_read().unwrap()).addr();
+    std::mem::drop(owned);
+    assert_eq!(Recycling::cached(), 32);
+
+    let owned = TestBox::new([2u64; 4]);
+    assert_eq!(
+        std::ptr::from_ref(&*owned.try_read().unwrap()).addr(),
+        first
+    );
+    assert_eq!(Recycling::cached(), 0);
+    // Recycled memory can still be taken over by a box.
+    assert_eq!(*owned.try_into_box().unwrap(), [2; 4]);
+}
+
+#[test]
+fn recycling_is_bounded() {
+    use crate::alloc::Recycling;
+
+    let vec = (0..4)
+        .map(|i| TestBox::new_in([i as u64; 4], Recycling))
+        .collect::<Vec<_>>();
+    Recycling::set_limit(64);
+    std::mem::drop(vec);
+    assert_eq!(Recycling::cached(), 64);
+
+    Recycling::set_limit(40);
+    assert_eq!(Recycling::cached(), 32);
+    std::thread::spawn(|| assert_eq!(Recycling::cached(), 0))
+        .join()
+        .unwrap();
+}
+
+#[test]
+#[cfg(feature = "nightly")]
+fn std_allocators() {
+    use std::alloc::System;
+
+    let owned = TestBox::new_in([1, 2, 3], System);
+    let slice: TestBox<[i32], System> = owned;
+    assert_eq!(slice.try_read().unwrap().len(), 3);
+}
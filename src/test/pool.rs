@@ -0,0 +1,457 @@
+use std::rc::Rc;
+
+use ralc_internals::accounts::balances::Poisoning;
+
+use crate::{LocalPool, PoolAccount, PoolAllocator, RalcMut, RalcPtr, RalcRef, SyncPool};
+
+use super::*;
+
+#[test]
+fn predictable_allocation_count_pool() {
+    let pool = LocalPool::new();
+    predictable_allocation_count(
+        |i| pool.ralc(i),
+        || {},
+        || pool.total_allocations(),
+        || pool.free_count(),
+    );
+}
+
+#[test]
+fn borrows_dont_allocate_pool() {
+    let pool = LocalPool::new();
+    borrows_dont_allocate(|i| pool.ralc(i), || {}, || pool.total_allocations());
+}
+
+#[test]
+fn handles_are_two_words_pool() {
+    let pool = LocalPool::new();
+    handles_are_two_words(|b| pool.ralc_box(b));
+    let pool = SyncPool::new();
+    handles_are_two_words(|b| pool.ralc_box(b));
+}
+
+#[test]
+fn test_pool_write_read() {
+    let pool = LocalPool::new();
+    test_write_read(pool.ralc(0));
+    let pool = SyncPool::new();
+    test_write_read(pool.ralc(0));
+}
+
+#[test]
+fn test_pool_into_inner() {
+    let pool = LocalPool::new();
+    test_into_inner(pool.ralc(0));
+    let pool = SyncPool::new();
+    test_into_inner(pool.ralc(0));
+}
+
+#[test]
+fn chunks_grow_to_limit_pool() {
+    let pool = LocalPool::new();
+    pool.set_chunks(4, 8);
+
+    let vec = (0..4 + 8 + 8).map(|i| pool.ralc(i)).collect::<Vec<_>>();
+    assert_eq!(pool.expansions(), 3);
+    assert_eq!(pool.outstanding(), 20);
+    std::mem::drop(vec);
+    assert_eq!(pool.free_count(), 20);
+    assert_eq!(pool.outstanding(), 0);
+}
+
+#[test]
+fn forgotten_ralcs_outlive_pool() {
+    let drops = Rc::new(Cell::new(0));
+    let pool = LocalPool::new();
+    let owned = pool.ralc(DropCount(drops.clone()));
+    std::mem::forget(owned.try_read().unwrap());
+    std::mem::drop(owned);
+    assert_eq!(pool.outstanding(), 1);
+    std::mem::drop(pool);
+    assert_eq!(drops.get(), 0);
+}
+
+#[test]
+fn split_guards_across_threads() {
+    let pool = SyncPool::new();
+    let owned = pool.ralc(vec![0; 64]);
+
+    let parts = owned
+        .try_write()
+        .unwrap()
+        .split_parts(|vec| vec.chunks_mut(16).collect());
+    std::thread::scope(|s| {
+        for (i, mut part) in parts.into_iter().enumerate() {
+            s.spawn(move || part.fill(i));
+        }
+    });
+
+    let data = owned.try_read().unwrap();
+    assert!(
+        data.chunks(16)
+            .enumerate()
+            .all(|(i, c)| c.iter().all(|&x| x == i))
+    );
+}
+
+#[test]
+fn panicking_split_guard_poisons() {
+    use std::sync::atomic::{AtomicU32, AtomicU64};
+
+    let pool = PoolAllocator::<Poisoning<AtomicU64>, AtomicU32>::new();
+    let owned = pool.ralc((0, 0));
+    let (a, b) = owned.try_write().unwrap().map_split(|(a, b)| (a, b));
+
+    std::thread::scope(|s| {
+        assert!(
+            s.spawn(move || {
+                let _a = a;
+                panic!()
+            })
+            .join()
+            .is_err()
+        );
+    });
+    std::mem::drop(b);
+    assert!(owned.is_poisoned());
+}
+
+#[test]
+fn sync_pool_shared_across_threads() {
+    let pool = SyncPool::new();
+    let owned = (0..4).map(|_| pool.ralc(0)).collect::<Vec<_>>();
+
+    std::thread::scope(|s| {
+        for owned in &owned {
+            let ptr = owned.borrow();
+            s.spawn(move || {
+                for _ in 0..1000 {
+                    *ptr.try_write().unwrap() += 1;
+                }
+            });
+        }
+        s.spawn(|| std::mem::drop(pool.ralc(0)));
+    });
+
+    for owned in owned {
+        assert_eq!(*owned.try_read().unwrap(), 1000);
+    }
+    assert_eq!(pool.outstanding(), 0);
+}
+
+/// The last of several readers relinquished at once, or the owner racing them, must drop
+/// the data exactly once. Every other round, one of them holds the upgradable permit.
+#[test]
+fn last_reader_frees_across_threads() {
+    use std::sync::{
+        Barrier,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    #[derive(Debug)]
+    struct Counted<'a>(&'a AtomicUsize);
+
+    impl Drop for Counted<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[cfg(not(miri))]
+    const ROUNDS: usize = 2_000;
+    #[cfg(miri)]
+    const ROUNDS: usize = 10;
+    const READERS: usize = 4;
+
+    let drops = AtomicUsize::new(0);
+    let pool = SyncPool::new();
+    let barrier = &Barrier::new(READERS + 1);
+    for round in 0..ROUNDS {
+        let owned = pool.ralc(Counted(&drops));
+        let ptr = owned.borrow();
+        std::thread::scope(|s| {
+            for reader in 0..READERS {
+                s.spawn(move || {
+                    let (rd, up) = if reader == 0 && round % 4 >= 2 {
+                        (None, Some(ptr.try_upgradable_read().unwrap()))
+                    } else {
+                        (Some(ptr.try_read().unwrap()), None)
+                    };
+                    barrier.wait();
+                    barrier.wait();
+                    std::mem::drop((rd, up));
+                });
+            }
+
+            barrier.wait();
+            if round % 2 == 0 {
+                std::mem::drop(owned);
+                barrier.wait();
+            } else {
+                barrier.wait();
+                std::mem::drop(owned);
+            }
+        });
+        assert_eq!(drops.load(Ordering::Relaxed), round + 1);
+    }
+    assert_eq!(pool.outstanding(), 0);
+}
+
+#[test]
+fn waitable_pool_async_contention() {
+    use ralc_internals::accounts::waiting::Waitable;
+    use std::sync::atomic::{AtomicU32, AtomicU64};
+
+    let pool = PoolAllocator::<AtomicU64, Waitable<AtomicU32>>::new();
+    let owned = pool.ralc(0);
+    let ptr = owned.borrow();
+
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(move || {
+                block_on(async {
+                    for _ in 0..1000 {
+                        let mut wr = ptr.write_async().await.unwrap();
+                        *wr += 1;
+                        tokio::task::yield_now().await;
+                    }
+                })
+            });
+        }
+    });
+
+    assert_eq!(*owned.try_read().unwrap(), 4000);
+}
+
+#[test]
+fn reader_preferring_admits_readers() {
+    use ralc_internals::accounts::fairness::ReaderPreferring;
+
+    let pool = PoolAllocator::<Cell<u64>, ReaderPreferring<Cell<u32>>>::new();
+    let owned = pool.ralc(0);
+    let ptr = owned.borrow();
+
+    let rd = ptr.try_read().unwrap();
+    assert_eq!(ptr.try_write().unwrap_err(), NoAccess::Blocked);
+    let rd2 = ptr.try_read().unwrap();
+    std::mem::drop((rd, rd2));
+    *ptr.try_write().unwrap() += 1;
+    test_write_read(pool.ralc(0));
+}
+
+#[test]
+fn writer_preferring_turns_away_readers() {
+    use ralc_internals::accounts::fairness::WriterPreferring;
+
+    let pool = PoolAllocator::<Cell<u64>, WriterPreferring<Cell<u32>>>::new();
+    let owned = pool.ralc(0);
+    let ptr = owned.borrow();
+
+    let rd = ptr.try_read().unwrap();
+    assert_eq!(ptr.try_write().unwrap_err(), NoAccess::Blocked);
+    assert_eq!(ptr.try_read().unwrap_err(), NoAccess::Blocked);
+    assert_eq!(ptr.try_upgradable_read().unwrap_err(), NoAccess::Blocked);
+    // Readers already in may still share their access.
+    let rd2 = rd.clone();
+    std::mem::drop((rd, rd2));
+    *ptr.try_write().unwrap() += 1;
+
+    // Once the readers are gone, a writer which gave up no longer holds them back.
+    let rd = ptr.try_read().unwrap();
+    assert_eq!(ptr.try_write().unwrap_err(), NoAccess::Blocked);
+    std::mem::drop(rd);
+    let rd = ptr.try_read().unwrap();
+    let rd2 = ptr.try_read().unwrap();
+    assert_eq!(*rd + *rd2, 2);
+    std::mem::drop((rd, rd2));
+    test_write_read(pool.ralc(0));
+}
+
+#[test]
+fn eventually_fair_alternates() {
+    use ralc_internals::accounts::fairness::EventuallyFair;
+
+    let pool = PoolAllocator::<Cell<u64>, EventuallyFair<Cell<u32>>>::new();
+    let owned = pool.ralc(0);
+    let ptr = owned.borrow();
+
+    let wr = ptr.try_write().unwrap();
+    assert_eq!(ptr.try_read().unwrap_err(), NoAccess::Blocked);
+    std::mem::drop(wr);
+    // The next writer makes way for the reader turned away.
+    assert_eq!(ptr.try_write().unwrap_err(), NoAccess::Blocked);
+    let rd = ptr.try_read().unwrap();
+    assert_eq!(ptr.try_write().unwrap_err(), NoAccess::Blocked);
+    // ...and readers make way for the writer in turn.
+    assert_eq!(ptr.try_read().unwrap_err(), NoAccess::Blocked);
+    std::mem::drop(rd);
+    *ptr.try_write().unwrap() += 1;
+    assert_eq!(*ptr.try_read().unwrap(), 1);
+    test_write_read(pool.ralc(0));
+}
+
+/// Drop a ralc after a reader was turned away during a write, which must not leave
+/// the payload or account behind for a reader who gave up.
+fn drop_after_turned_away_reader<P: Permits + Default>() {
+    let pool = PoolAllocator::<Cell<u64>, P>::new();
+    let drops = Rc::new(Cell::new(0));
+    let owned = pool.ralc(DropCount(drops.clone()));
+    let ptr = owned.borrow();
+
+    let wr = ptr.try_write().unwrap();
+    assert_eq!(ptr.try_read().unwrap_err(), NoAccess::Blocked);
+    std::mem::drop(wr);
+    std::mem::drop(owned);
+    assert_eq!(drops.get(), 1);
+    assert_eq!(pool.outstanding(), 0);
+}
+
+#[test]
+fn reader_preferring_drops_after_turned_away_reader() {
+    drop_after_turned_away_reader::<ralc_internals::accounts::fairness::ReaderPreferring<Cell<u32>>>(
+    );
+}
+
+#[test]
+fn writer_preferring_drops_after_turned_away_reader() {
+    drop_after_turned_away_reader::<ralc_internals::accounts::fairness::WriterPreferring<Cell<u32>>>(
+    );
+}
+
+#[test]
+fn eventually_fair_drops_after_turned_away_reader() {
+    drop_after_turned_away_reader::<ralc_internals::accounts::fairness::EventuallyFair<Cell<u32>>>(
+    );
+}
+
+#[test]
+fn policies_exclude_writers_across_threads() {
+    use ralc_internals::accounts::fairness::EventuallyFair;
+    use std::sync::atomic::{AtomicU32, AtomicU64};
+
+    let pool = PoolAllocator::<AtomicU64, EventuallyFair<AtomicU32>>::new();
+    let owned = pool.ralc((0, 0));
+    let ptr = owned.borrow();
+
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(move || {
+                let mut writes = 0;
+                while writes < 1000 {
+                    if let Ok(mut wr) = ptr.try_write() {
+                        let (a, b) = &mut *wr;
+                        *a += 1;
+                        *b += 1;
+                        writes += 1;
+                    } else if let Ok(rd) = ptr.try_read() {
+                        let (a, b) = *rd;
+                        assert_eq!(a, b);
+                    }
+                    std::hint::spin_loop();
+                }
+            });
+        }
+    });
+
+    assert_eq!(*owned.try_read().unwrap(), (4000, 4000));
+}
+
+#[test]
+fn panicking_writer_poisons() {
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+
+    let pool = PoolAllocator::<Poisoning<Cell<u64>>, Cell<u32>>::new();
+    let owned = pool.ralc(1);
+    let ptr = owned.borrow();
+
+    let res = catch_unwind(AssertUnwindSafe(|| {
+        let rd = ptr.try_read().unwrap();
+        assert_eq!(*rd, 1);
+        panic!("reader");
+    }));
+    assert!(res.is_err());
+    assert!(!owned.is_poisoned());
+
+    let res = catch_unwind(AssertUnwindSafe(|| {
+        let mut wr = ptr.try_write().unwrap();
+        *wr = 2;
+        panic!("writer");
+    }));
+    assert!(res.is_err());
+    assert!(owned.is_poisoned());
+    assert!(ptr.is_poisoned());
+
+    let err = ptr.try_read().unwrap_err();
+    assert!(matches!(err, NoAccess::Poisoned(_)));
+    let rd = err.into_poisoned().unwrap();
+    assert_eq!(*rd, 2);
+    std::mem::drop(rd);
+    assert!(matches!(owned.try_write(), Err(NoAccess::Poisoned(_))));
+
+    owned.clear_poison();
+    assert!(!ptr.is_poisoned());
+    assert_eq!(*ptr.try_read().unwrap(), 2);
+
+    let res = catch_unwind(AssertUnwindSafe(|| {
+        let _wr = owned.try_write().unwrap();
+        panic!("writer");
+    }));
+    assert!(res.is_err());
+    std::mem::drop(owned);
+    assert!(!ptr.is_poisoned());
+    assert_eq!(ptr.try_read().unwrap_err(), NoAccess::Stale);
+
+    let fresh = pool.ralc(3);
+    assert!(!fresh.is_poisoned());
+    assert_eq!(*fresh.try_read().unwrap(), 3);
+}
+
+#[test]
+fn poisoning_across_threads() {
+    use std::sync::atomic::{AtomicU32, AtomicU64};
+
+    let pool = PoolAllocator::<Poisoning<AtomicU64>, AtomicU32>::new();
+    let owned = pool.ralc(vec![1, 2]);
+    let ptr = owned.borrow();
+
+    let res = std::thread::scope(|s| {
+        s.spawn(move || {
+            let mut wr = ptr.try_write().unwrap();
+            wr.push(3);
+            panic!("writer");
+        })
+        .join()
+    });
+    assert!(res.is_err());
+
+    let wr = owned.try_write().unwrap_err().into_poisoned().unwrap();
+    assert_eq!(*wr, [1, 2, 3]);
+}
+
+#[test]
+fn unpoisonable_accounts_ignore_panics() {
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+
+    let pool = LocalPool::new();
+    let owned = pool.ralc(1);
+    let res = catch_unwind(AssertUnwindSafe(|| {
+        let _wr = owned.try_write().unwrap();
+        panic!("writer");
+    }));
+    assert!(res.is_err());
+    assert!(!owned.is_poisoned());
+    assert_eq!(*owned.try_read().unwrap(), 1);
+}
+
+#[test]
+fn send_sync() {
+    use assert_impl::assert_impl;
+    type Shared<'a> = PoolAccount<'a, std::sync::atomic::AtomicU64, std::sync::atomic::AtomicU32>;
+    type Local<'a> = PoolAccount<'a, Cell<u64>, Cell<u32>>;
+    assert_impl!(Send: SyncPool, RalcBox<i32, Shared<'static>>, RalcPtr<i32, Shared<'static>>);
+    assert_impl!(Sync: SyncPool, RalcRef<i32, Shared<'static>>, RalcMut<i32, Shared<'static>>);
+    assert_impl!(Send: LocalPool);
+    assert_impl!(!Send: RalcBox<i32, Local<'static>>, RalcPtr<i32, Local<'static>>);
+    assert_impl!(!Sync: LocalPool);
+}
@@ -0,0 +1,232 @@
+use std::{
+    rc::Rc,
+    sync::atomic::{AtomicU32, AtomicU64},
+};
+
+use crate::{LocalSlab, RalcIndex, SlabAccount, SyncSlab};
+
+use super::*;
+
+#[test]
+fn test_slab_write_read() {
+    let slab = LocalSlab::new();
+    test_write_read(slab.ralc(0));
+    let slab = SyncSlab::new();
+    test_write_read(slab.ralc(0));
+}
+
+#[test]
+fn test_slab_into_inner() {
+    let slab = LocalSlab::new();
+    let owned = slab.ralc(0);
+    *owned.try_write().unwrap() = 99;
+
+    let rd = owned.try_read().unwrap();
+    let owned = owned.try_into_inner().unwrap_err();
+    std::mem::drop(rd);
+
+    let ptr = owned.borrow();
+    assert_eq!(owned.try_into_inner().ok(), Some(99));
+    assert!(!ptr.check());
+    assert_eq!(slab.outstanding(), 0);
+}
+
+#[test]
+fn data_is_inline() {
+    let slab = LocalSlab::new();
+    let owned = slab.ralc([0u64; 4]);
+
+    let account = std::ptr::from_ref(owned.account()).addr();
+    let data = std::ptr::from_ref(&*owned.try_read().unwrap()).addr();
+    assert!(data >= account);
+    assert!(data < account + size_of::<SlabAccount<'_, [u64; 4], Cell<u64>, Cell<u32>>>());
+}
+
+#[test]
+fn handles_are_two_words() {
+    use crate::{RalcPtr, alloc::InPlace};
+
+    type Acc<'a> = SlabAccount<'a, String, Cell<u64>, Cell<u32>>;
+    assert_eq!(size_of::<RalcBox<String, Acc<'_>, InPlace>>(), 16);
+    assert_eq!(size_of::<RalcPtr<String, Acc<'_>, InPlace>>(), 16);
+    // Guards also carry their registry token when detecting deadlocks.
+    #[cfg(not(feature = "deadlock-detection"))]
+    assert_eq!(size_of::<crate::RalcMut<String, Acc<'_>, InPlace>>(), 16);
+    assert_eq!(size_of::<Option<RalcPtr<String, Acc<'_>, InPlace>>>(), 16);
+
+    let slab = LocalSlab::new();
+    let owned = slab.ralc(String::from("slab"));
+    let ptr = owned.borrow();
+    ptr.try_write().unwrap().push('s');
+    assert_eq!(
+        *slab.get(owned.index()).unwrap().try_read().unwrap(),
+        "slabs"
+    );
+    std::mem::drop(owned);
+    assert_eq!(ptr.try_read().unwrap_err(), NoAccess::Stale);
+}
+
+#[test]
+fn raw_parts_slab() {
+    use std::ptr::NonNull;
+
+    use crate::{RalcPtr, alloc::InPlace};
+
+    type Acc<'a> = SlabAccount<'a, i32, Cell<u64>, Cell<u32>>;
+    let slab = LocalSlab::new();
+    let owned = slab.ralc(1);
+    let (account, generation, data) = owned.borrow().into_raw_parts();
+    assert_eq!(data, NonNull::from_ref(&*owned.try_read().unwrap()));
+
+    let ptr =
+        unsafe { RalcPtr::<i32, Acc<'_>, InPlace>::from_raw_parts(account, generation, data) };
+    *ptr.unwrap().try_write().unwrap() += 1;
+    assert_eq!(*owned.try_read().unwrap(), 2);
+    assert_eq!(ptr.unwrap().index(), owned.index());
+}
+
+#[test]
+fn slots_are_reused() {
+    let drops = Rc::new(Cell::new(0));
+    let slab = LocalSlab::new();
+    let owned = slab.ralc(DropCount(drops.clone()));
+    let id = owned.account_id();
+    let ptr = owned.borrow();
+    let rd = ptr.try_read().unwrap();
+
+    std::mem::drop(owned);
+    assert_eq!(drops.get(), 0);
+    assert_eq!(slab.outstanding(), 1);
+
+    std::mem::drop(rd);
+    assert_eq!(drops.get(), 1);
+    assert_eq!(slab.outstanding(), 0);
+    assert_eq!(slab.free_count(), 1);
+
+    let owned = slab.ralc(DropCount(drops.clone()));
+    assert_eq!(owned.account_id(), id);
+    assert_eq!(ptr.try_read().unwrap_err(), NoAccess::Stale);
+    assert!(owned.try_read().is_ok());
+
+    std::mem::drop(owned);
+    assert_eq!(drops.get(), 2);
+}
+
+#[test]
+fn chunks_grow_to_limit_slab() {
+    let slab = LocalSlab::new();
+    slab.set_chunks(4, 8);
+
+    let vec = (0..4 + 8 + 8).map(|i| slab.ralc(i)).collect::<Vec<_>>();
+    assert_eq!(slab.expansions(), 3);
+    assert_eq!(slab.outstanding(), 20);
+    std::mem::drop(vec);
+    assert_eq!(slab.free_count(), 20);
+    assert_eq!(slab.outstanding(), 0);
+}
+
+#[test]
+fn sync_slab_shared_across_threads() {
+    let slab = SyncSlab::new();
+    let owned = (0..4).map(|_| slab.ralc(0)).collect::<Vec<_>>();
+
+    std::thread::scope(|s| {
+        for owned in &owned {
+            let ptr = owned.borrow();
+            s.spawn(move || {
+                for _ in 0..1000 {
+                    *ptr.try_write().unwrap() += 1;
+                }
+            });
+        }
+        s.spawn(|| std::mem::drop(slab.ralc(0)));
+    });
+
+    for owned in owned {
+        assert_eq!(*owned.try_read().unwrap(), 1000);
+    }
+    assert_eq!(slab.outstanding(), 0);
+}
+
+#[test]
+fn index_handles() {
+    assert_eq!(size_of::<RalcIndex<String>>(), 8);
+    assert_eq!(size_of::<Option<RalcIndex<String>>>(), 8);
+
+    let slab = LocalSlab::new();
+    let owned = (0..200).map(|i| slab.ralc(i)).collect::<Vec<_>>();
+    let indexes = owned.iter().map(|owned| owned.index()).collect::<Vec<_>>();
+    for (i, &index) in indexes.iter().enumerate() {
+        let bits = RalcIndex::from_bits(index.into_bits()).unwrap();
+        let ptr = slab.get(bits).unwrap();
+        assert_eq!(ptr.index(), index);
+        assert_eq!(*ptr.try_read().unwrap(), i);
+    }
+    assert_eq!(RalcIndex::<usize>::from_bits(0), None);
+}
+
+#[test]
+fn index_handles_go_stale() {
+    let slab = LocalSlab::new();
+    let owned = slab.ralc(1);
+    let index = owned.index();
+    let rd = owned.try_read().unwrap();
+
+    std::mem::drop(owned);
+    assert!(slab.get(index).is_none());
+    std::mem::drop(rd);
+    assert!(slab.get(index).is_none());
+
+    let owned = slab.ralc(2);
+    assert_eq!(owned.index().index(), index.index());
+    assert_ne!(owned.index(), index);
+    // Each reuse of a slot advances its generation by one.
+    assert_eq!(owned.index().generation(), index.generation() + 1);
+    assert!(slab.get(index).is_none());
+    assert_eq!(*slab.get(owned.index()).unwrap().try_read().unwrap(), 2);
+
+    // Forged handles only ever resolve to the owned ralc.
+    let slot = index.index() as u64 + 1;
+    let resolved = (0..64)
+        .filter_map(|generation| slab.get(RalcIndex::from_bits(generation << 32 | slot)?))
+        .collect::<Vec<_>>();
+    assert_eq!(resolved.len(), 1);
+    assert_eq!(resolved[0].index(), owned.index());
+    std::mem::drop(owned);
+    assert!((0..64).all(|g| {
+        slab.get(RalcIndex::from_bits(g << 32 | slot).unwrap())
+            .is_none()
+    }));
+}
+
+#[test]
+fn index_handles_across_threads() {
+    let slab = SyncSlab::new();
+    let first = slab.ralc(0);
+
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                let owned = (0..100).map(|i| slab.ralc(i)).collect::<Vec<_>>();
+                for (i, owned) in owned.iter().enumerate() {
+                    assert_eq!(*slab.get(owned.index()).unwrap().try_read().unwrap(), i);
+                }
+                *slab.get(first.index()).unwrap().write().unwrap() += 1;
+            });
+        }
+    });
+    assert_eq!(*first.try_read().unwrap(), 4);
+}
+
+#[test]
+fn send_sync_slab() {
+    use crate::{RalcMut, RalcPtr, RalcRef, alloc::InPlace};
+    use assert_impl::assert_impl;
+
+    type Acc<'a> = SlabAccount<'a, i32, AtomicU64, AtomicU32>;
+    type LocalAcc<'a> = SlabAccount<'a, i32, Cell<u64>, Cell<u32>>;
+    assert_impl!(Send: SyncSlab<i32>, RalcBox<i32, Acc<'_>, InPlace>, RalcPtr<i32, Acc<'_>, InPlace>);
+    assert_impl!(Sync: SyncSlab<i32>, RalcRef<i32, Acc<'_>, InPlace>, RalcMut<i32, Acc<'_>, InPlace>);
+    assert_impl!(!Send: RalcBox<i32, LocalAcc<'_>, InPlace>);
+    assert_impl!(!Sync: LocalSlab<i32>);
+}
@@ -0,0 +1,157 @@
+#[cfg(feature = "parking-lot")]
+use crate::{Global, GlobalAllocator, test::global::MUTEX};
+use crate::{LocalPool, ThreadLocal, ThreadLocalAllocator};
+
+use super::*;
+
+#[cfg(not(miri))]
+const N: usize = 100_000;
+
+#[cfg(miri)]
+const N: usize = 100;
+
+const ROUNDS: usize = 10;
+
+/// Move boxes into ralcs and back for several rounds, which must only expand the book in
+/// the first round, as later rounds reuse the accounts freed before.
+fn stress_test_2<A: Account>(
+    new: impl Fn(Box<usize>) -> RalcBox<usize, A>,
+    expansions: impl Fn() -> usize,
+    total_allocations: impl Fn() -> usize,
+) {
+    let mut vec = Vec::with_capacity(N);
+    let mut vec2 = Vec::with_capacity(N);
+    for i in 0..N {
+        vec.push(Box::new(i));
+    }
+    let mut first = None;
+    for _ in 0..ROUNDS {
+        for b in vec.drain(..) {
+            vec2.push(new(b));
+        }
+        assert_eq!(*first.get_or_insert_with(&expansions), expansions());
+
+        for b in vec2.drain(..) {
+            vec.push(b.try_into_box().unwrap())
+        }
+    }
+    assert!(vec.iter().enumerate().all(|(i, b)| **b == i));
+    assert_eq!(total_allocations(), ROUNDS * N);
+}
+
+#[test]
+#[cfg(feature = "parking-lot")]
+fn stress_test_2_global() {
+    let _lock = MUTEX.lock();
+    GlobalAllocator::reset();
+    stress_test_2(
+        RalcBox::<_, Global>::from_box,
+        GlobalAllocator::expansions,
+        GlobalAllocator::total_allocations,
+    );
+}
+
+#[test]
+fn stress_test_2_thread_local() {
+    ThreadLocalAllocator::reset();
+    stress_test_2(
+        RalcBox::<_, ThreadLocal>::from_box,
+        ThreadLocalAllocator::expansions,
+        ThreadLocalAllocator::total_allocations,
+    );
+}
+
+#[test]
+fn stress_test_2_pool() {
+    let pool = LocalPool::new();
+    stress_test_2(
+        |b| pool.ralc_box(b),
+        || pool.expansions(),
+        || pool.total_allocations(),
+    );
+}
+
+/// Many threads churning through global accounts at once must never be handed the same
+/// account, nor lose any of them.
+#[test]
+#[cfg(feature = "parking-lot")]
+fn stress_test_global_threads() {
+    stress_test_global_threads_with_magazine(0);
+}
+
+/// As above, with every thread caching accounts in its magazine.
+#[test]
+#[cfg(feature = "parking-lot")]
+fn stress_test_global_threads_magazine() {
+    stress_test_global_threads_with_magazine(32);
+}
+
+#[cfg(feature = "parking-lot")]
+fn stress_test_global_threads_with_magazine(size: usize) {
+    #[cfg(not(miri))]
+    const ROUNDS: usize = 2_000;
+    #[cfg(miri)]
+    const ROUNDS: usize = 4;
+    const THREADS: usize = 8;
+    const BATCH: usize = 64;
+
+    let _lock = MUTEX.lock();
+    GlobalAllocator::reset();
+    GlobalAllocator::set_chunks(16, 64);
+    GlobalAllocator::set_magazine_size(size);
+
+    std::thread::scope(|s| {
+        let threads = (0..THREADS)
+            .map(|t| {
+                s.spawn(move || {
+                    for round in 0..ROUNDS {
+                        let batch = (0..BATCH)
+                            .map(|i| RalcBox::<_, Global>::new((t, round, i)))
+                            .collect::<Vec<_>>();
+                        let ptrs = batch.iter().map(RalcBox::borrow).collect::<Vec<_>>();
+
+                        // An account shared with another live ralc would either be
+                        // invalidated or locked by it.
+                        let writers = ptrs
+                            .iter()
+                            .map(|ptr| ptr.try_write().unwrap())
+                            .collect::<Vec<_>>();
+                        for (i, wr) in writers.iter().enumerate() {
+                            assert_eq!(**wr, (t, round, i));
+                        }
+                        std::mem::drop(writers);
+                        std::mem::drop(batch);
+                        assert!(ptrs.iter().all(|ptr| !ptr.check()));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Joining, unlike leaving the scope, waits for the magazines to be flushed.
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    });
+    GlobalAllocator::set_magazine_size(0);
+
+    if size == 0 {
+        assert_eq!(
+            GlobalAllocator::total_allocations(),
+            THREADS * ROUNDS * BATCH
+        );
+    }
+
+    // Every account handed out was returned, so all of them can be reused
+    // without leaking another chunk.
+    let expansions = GlobalAllocator::expansions();
+    let free = GlobalAllocator::free_count();
+    assert!(free >= BATCH);
+    let all = (0..free).map(RalcBox::<_, Global>::new).collect::<Vec<_>>();
+    assert_eq!(GlobalAllocator::expansions(), expansions);
+    assert_eq!(GlobalAllocator::free_count(), 0);
+    // No account was on the free list twice.
+    assert!(all.iter().all(|owned| owned.borrow().check()));
+    std::mem::drop(all);
+
+    GlobalAllocator::set_chunks(CHUNK_SIZE, CHUNK_SIZE * CHUNK_SIZE);
+}
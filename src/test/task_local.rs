@@ -0,0 +1,143 @@
+use std::rc::Rc;
+
+use crate::{FutureExt, RalcMut, RalcPtr, RalcRef, TaskLocal, TaskLocalAllocator};
+
+use super::*;
+
+#[test]
+fn predictable_allocation_count_task_local() {
+    block_on(
+        async {
+            predictable_allocation_count(
+                RalcBox::<_, TaskLocal>::new,
+                TaskLocalAllocator::reset,
+                TaskLocalAllocator::total_allocations,
+                TaskLocalAllocator::free_count,
+            )
+        }
+        .with_ralcs(),
+    );
+}
+
+#[test]
+fn borrows_dont_allocate_task_local() {
+    block_on(
+        async {
+            borrows_dont_allocate(
+                RalcBox::<_, TaskLocal>::new,
+                TaskLocalAllocator::reset,
+                TaskLocalAllocator::total_allocations,
+            )
+        }
+        .with_ralcs(),
+    );
+}
+
+#[test]
+fn handles_are_two_words_task_local() {
+    block_on(async { handles_are_two_words(RalcBox::<_, TaskLocal>::from_box) }.with_ralcs());
+}
+
+#[test]
+fn test_task_local_write_read() {
+    block_on(async { test_write_read(RalcBox::<_, TaskLocal>::new(0)) }.with_ralcs());
+}
+
+#[test]
+fn test_task_local_into_inner() {
+    block_on(async { test_into_inner(RalcBox::<_, TaskLocal>::new(0)) }.with_ralcs());
+}
+
+#[test]
+#[should_panic = "with_ralcs"]
+fn allocation_outside_scope_panics() {
+    RalcBox::<_, TaskLocal>::new(0);
+}
+
+#[test]
+fn scopes_count_their_own_allocations() {
+    block_on(
+        async {
+            let owned = RalcBox::<_, TaskLocal>::new(0);
+            assert_eq!(TaskLocalAllocator::outstanding(), Some(1));
+
+            let inner = async {
+                let owned = RalcBox::<_, TaskLocal>::new(1);
+                assert_eq!(TaskLocalAllocator::outstanding(), Some(1));
+                owned
+            }
+            .with_ralcs()
+            .await;
+
+            assert_eq!(TaskLocalAllocator::outstanding(), Some(1));
+            std::mem::drop(inner);
+            assert_eq!(TaskLocalAllocator::outstanding(), Some(1));
+            std::mem::drop(owned);
+            assert_eq!(TaskLocalAllocator::outstanding(), Some(0));
+        }
+        .with_ralcs(),
+    );
+    assert_eq!(TaskLocalAllocator::outstanding(), None);
+}
+
+#[test]
+fn handles_outlive_scope() {
+    let drops = Rc::new(Cell::new(0));
+    let (owned, rd) = block_on(
+        async {
+            let owned = RalcBox::<_, TaskLocal>::new(DropCount(drops.clone()));
+            let rd = owned.try_read().unwrap();
+            (owned, rd)
+        }
+        .with_ralcs(),
+    );
+
+    let ptr = owned.borrow();
+    std::mem::drop(owned);
+    assert!(!ptr.check());
+    assert_eq!(drops.get(), 0);
+
+    let free = TaskLocalAllocator::free_count();
+    std::mem::drop(rd);
+    assert_eq!(drops.get(), 1);
+    assert_eq!(TaskLocalAllocator::free_count(), free + 1);
+}
+
+#[test]
+fn accounts_reused_across_scopes() {
+    TaskLocalAllocator::reset();
+    block_on(async { std::mem::drop(RalcBox::<_, TaskLocal>::new(0)) }.with_ralcs());
+    block_on(async { std::mem::drop(RalcBox::<_, TaskLocal>::new(0)) }.with_ralcs());
+    assert_eq!(TaskLocalAllocator::total_allocations(), 2);
+    assert_eq!(TaskLocalAllocator::expansions(), 1);
+}
+
+#[test]
+fn held_across_await() {
+    let local = tokio::task::LocalSet::new();
+    let res = block_on(local.run_until(async {
+        let owned = tokio::task::spawn_local(
+            async {
+                let owned = RalcBox::<_, TaskLocal>::new(0);
+                let ptr = owned.borrow();
+                for _ in 0..10 {
+                    *ptr.try_write().unwrap() += 1;
+                    tokio::task::yield_now().await;
+                }
+                owned
+            }
+            .with_ralcs(),
+        );
+        *owned.await.unwrap().try_read().unwrap()
+    }));
+    assert_eq!(res, 10);
+}
+
+#[test]
+fn send_sync() {
+    use assert_impl::assert_impl;
+    assert_impl!(!Send: RalcBox<i32, TaskLocal>, RalcPtr<i32, TaskLocal>, RalcRef<i32, TaskLocal>, RalcMut<i32, TaskLocal>);
+
+    fn is_send<T: Send>(_: T) {}
+    is_send(async { std::mem::drop(RalcBox::<_, TaskLocal>::new(0)) }.with_ralcs());
+}
@@ -0,0 +1,176 @@
+use std::cell::{Cell, RefCell};
+
+use ralc_internals::{
+    accounts::{
+        AccPtr, Account,
+        freeable::Freeable,
+        permits::Permits,
+        slots::{DataSlot, ErasedPtr, InAccount},
+    },
+    delegate_account_impl,
+    delegate_impl::DelegateAccountImpl,
+};
+
+use crate::ImplicitAccount;
+
+#[cfg(not(feature = "bumpalo"))]
+type AccountBook<N> = crate::ledgers::RetainingBook<N>;
+#[cfg(feature = "bumpalo")]
+type AccountBook<N> = crate::ledgers::BumpyBook<N>;
+
+thread_local! {
+    // Weak pointers are not counted as outstanding, and may be kept in other thread-locals
+    // which are torn down later, so the accounts are never released.
+    static THREAD_LOCAL_ALLOCATOR: Book = const {
+        Book(RefCell::new(AccountBook::leaking(ThreadLocalAccount::new as fn() -> _)))
+    };
+}
+
+/// Account type for ralcs which are confined to the thread that created them.
+///
+/// Thread-local accounts take no synchronized locks, and their ralcs are
+/// never `Send`. The accounts are leaked when the thread exits, as ralcs and
+/// [`RalcPtr`](crate::RalcPtr)s kept in other thread-locals may still refer
+/// to them. Ralcs dropped after the allocator has been torn down release their
+/// data as normal, but their accounts are not reused.
+pub type ThreadLocal = ThreadLocalAccount;
+
+/// Handle for tuning the current thread's allocator of [`ThreadLocal`] accounts.
+pub struct ThreadLocalAllocator;
+
+impl ThreadLocalAllocator {
+    /// Set the number of accounts allocated in the next chunk, and
+    /// the limit to which subsequent chunk sizes may grow.
+    pub fn set_chunks(chunk: usize, limit: usize) {
+        THREAD_LOCAL_ALLOCATOR.with(|book| book.0.borrow_mut().set_chunks(chunk, limit))
+    }
+
+    /// Number of thread-local accounts on this thread currently tracking an allocation.
+    pub fn outstanding() -> usize {
+        THREAD_LOCAL_ALLOCATOR.with(|book| book.0.borrow().outstanding())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn total_allocations() -> usize {
+        THREAD_LOCAL_ALLOCATOR.with(|book| book.0.borrow().total_allocations())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn expansions() -> usize {
+        THREAD_LOCAL_ALLOCATOR.with(|book| book.0.borrow().expansions())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn free_count() -> usize {
+        THREAD_LOCAL_ALLOCATOR.with(|book| book.0.borrow().free_count())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn reset() {
+        THREAD_LOCAL_ALLOCATOR.with(|book| book.0.borrow_mut().reset())
+    }
+}
+
+struct Book(RefCell<AccountBook<fn() -> ThreadLocalAccount>>);
+
+/// Outstanding account counts of books torn down on thread exit.
+#[cfg(test)]
+pub(crate) static EXITS: std::sync::Mutex<Vec<(std::thread::ThreadId, usize)>> =
+    std::sync::Mutex::new(Vec::new());
+
+#[cfg(test)]
+impl Drop for Book {
+    fn drop(&mut self) {
+        let outstanding = self.0.get_mut().outstanding();
+        if let Ok(mut exits) = EXITS.lock() {
+            exits.push((std::thread::current().id(), outstanding));
+        }
+    }
+}
+
+pub struct ThreadLocalAccount(Cell<i32>, Cell<u32>, Cell<ErasedPtr>);
+
+impl ThreadLocalAccount {
+    const fn new() -> Self {
+        Self(Cell::new(0), Cell::new(0), Cell::new(ErasedPtr::DANGLING))
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_balance(&self, value: i32) {
+        self.0.set(value)
+    }
+}
+
+impl DelegateAccountImpl for ThreadLocalAccount {
+    type DelegatedBalance = Cell<i32>;
+
+    type DelegatedPermits = Cell<u32>;
+
+    fn balance(&self) -> &Self::DelegatedBalance {
+        &self.0
+    }
+
+    fn permits(&self) -> &Self::DelegatedPermits {
+        &self.1
+    }
+}
+
+delegate_account_impl!(ThreadLocalAccount);
+
+unsafe impl Freeable for ThreadLocalAccount {
+    unsafe fn free(&self) {
+        unsafe {
+            // SAFETY:
+            // Guaranteed by caller.
+            self.abandon_mutation();
+        }
+
+        // If the allocator is already torn down, the account is leaked.
+        let _ = THREAD_LOCAL_ALLOCATOR.try_with(|book| unsafe {
+            // SAFETY:
+            // 1. Thread-local accounts are only allocated by this thread's book,
+            //    and the book was not torn down since
+            // 2. Guaranteed by caller
+            // 3. Guaranteed by caller
+            book.0.borrow_mut().deallocate(AccPtr::new(self))
+        });
+        // IMPL SAFETY:
+        // 1. See above
+    }
+}
+
+impl Account for ThreadLocalAccount {
+    type Data = InAccount;
+}
+
+// SAFETY:
+// 1. Delegated implementation
+unsafe impl DataSlot for ThreadLocalAccount {
+    #[inline]
+    unsafe fn set_data(&self, data: ErasedPtr) {
+        unsafe {
+            // SAFETY:
+            // 1. Guaranteed by caller
+            self.2.set_data(data)
+        }
+    }
+
+    #[inline]
+    fn data(&self) -> ErasedPtr {
+        self.2.data()
+    }
+}
+
+impl ImplicitAccount for ThreadLocalAccount {
+    fn allocate() -> AccPtr<Self> {
+        // Book chunks are leaked at thread exit. This satisfies 1.2 as
+        // `AccPtr<ThreadLocalAccount>: !Send`
+        THREAD_LOCAL_ALLOCATOR
+            .try_with(|book| book.0.borrow_mut().allocate())
+            .unwrap_or_else(|_| unsafe {
+                // SAFETY:
+                // 1.1 Leaked
+                AccPtr::new(Box::leak(Box::new(ThreadLocalAccount::new())))
+            })
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use ralc_internals::{
+    accounts::Account,
+    alloc::{self, Allocator},
+};
+
+use crate::{NoAccess, RalcMut, RalcPtr, RalcRef, Result};
+
+/// Future returned by [`RalcPtr::read_async`].
+///
+/// Dropping it before it resolves gives up waiting without acquiring anything.
+#[must_use = "futures do nothing unless polled"]
+pub struct ReadAsync<T: ?Sized, A: Account, Alloc: Allocator = alloc::Global> {
+    ptr: RalcPtr<T, A, Alloc>,
+    key: Option<u64>,
+}
+
+/// Future returned by [`RalcPtr::write_async`].
+///
+/// Dropping it before it resolves gives up waiting without acquiring anything.
+#[must_use = "futures do nothing unless polled"]
+pub struct WriteAsync<T: ?Sized, A: Account, Alloc: Allocator = alloc::Global> {
+    ptr: RalcPtr<T, A, Alloc>,
+    key: Option<u64>,
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> RalcPtr<T, A, Alloc> {
+    /// Get a readable reference through this pointer, waiting asynchronously for access.
+    /// Fails with [`NoAccess::Unsupported`] if the account does not support waiting.
+    ///
+    /// Fails with [`NoAccess::Stale`] if the owner dropped the data while waiting.
+    pub fn read_async(&self) -> ReadAsync<T, A, Alloc> {
+        ReadAsync {
+            ptr: *self,
+            key: None,
+        }
+    }
+
+    /// Get a writable reference through this pointer, waiting asynchronously for access.
+    /// Fails with [`NoAccess::Unsupported`] if the account does not support waiting.
+    ///
+    /// Fails with [`NoAccess::Stale`] if the owner dropped the data while waiting.
+    pub fn write_async(&self) -> WriteAsync<T, A, Alloc> {
+        WriteAsync {
+            ptr: *self,
+            key: None,
+        }
+    }
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> Future for ReadAsync<T, A, Alloc> {
+    type Output = Result<RalcRef<T, A, Alloc>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        poll_acquire(&this.ptr, &mut this.key, cx, RalcPtr::try_read)
+    }
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> Future for WriteAsync<T, A, Alloc> {
+    type Output = Result<RalcMut<T, A, Alloc>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        poll_acquire(&this.ptr, &mut this.key, cx, RalcPtr::try_write)
+    }
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> Drop for ReadAsync<T, A, Alloc> {
+    fn drop(&mut self) {
+        deregister(&self.ptr, &mut self.key);
+    }
+}
+
+impl<T: ?Sized, A: Account, Alloc: Allocator> Drop for WriteAsync<T, A, Alloc> {
+    fn drop(&mut self) {
+        deregister(&self.ptr, &mut self.key);
+    }
+}
+
+fn poll_acquire<T: ?Sized, A: Account, Alloc: Allocator, R>(
+    ptr: &RalcPtr<T, A, Alloc>,
+    key: &mut Option<u64>,
+    cx: &mut Context<'_>,
+    try_acquire: impl Fn(&RalcPtr<T, A, Alloc>) -> Result<R>,
+) -> Poll<Result<R>> {
+    match try_acquire(ptr) {
+        Err(NoAccess::Blocked) => {}
+        res => {
+            deregister(ptr, key);
+            return Poll::Ready(res);
+        }
+    }
+
+    let Some(queue) = ptr.0.account().wait_queue() else {
+        return Poll::Ready(Err(NoAccess::Unsupported));
+    };
+    queue.register(key, cx.waker());
+
+    // Retry, in case the permits were relinquished before the waker was registered.
+    match try_acquire(ptr) {
+        Err(NoAccess::Blocked) => Poll::Pending,
+        res => {
+            queue.deregister(key);
+            Poll::Ready(res)
+        }
+    }
+}
+
+fn deregister<T: ?Sized, A: Account, Alloc: Allocator>(
+    ptr: &RalcPtr<T, A, Alloc>,
+    key: &mut Option<u64>,
+) {
+    if let Some(queue) = ptr.0.account().wait_queue() {
+        queue.deregister(key);
+    }
+}